@@ -1,5 +1,5 @@
 
-use tracing::{ Level};
+use tracing::{ Level, info, warn};
 use tracing_subscriber::{
     prelude::*,
     fmt,
@@ -10,7 +10,15 @@ use tracing_subscriber::{
 use clap::Parser;
 
 
-use agent_discovery_service::discovery_server::server::DiscoveryServer;
+use std::env;
+use std::time::Duration;
+
+use agent_discovery_service::discovery_server::auth::AuthConfig;
+use agent_discovery_service::discovery_server::duplicates::DuplicateDetectionConfig;
+use agent_discovery_service::discovery_server::reputation::ReputationSync;
+use agent_discovery_service::discovery_server::server::{DiscoveryServer, DiscoveryServerConfig};
+use agent_discovery_service::discovery_server::static_config::StaticConfigWatcher;
+use agent_discovery_service::discovery_server::webhooks::WebhookConfig;
 
 /// Command-line arguments for the reimbursement server
 #[derive(Parser, Debug)]
@@ -20,6 +28,39 @@ struct Args {
     log_level: String,
     #[clap(long, default_value = "0.0.0.0:4000")]
     uri: String,
+    /// Registry snapshot (JSON) to preload at startup
+    #[clap(long)]
+    seed_file: Option<String>,
+    /// Directory of static agent/task/tool registrations (TOML, JSON or YAML)
+    #[clap(long)]
+    static_config_dir: Option<String>,
+    /// Polling interval, in seconds, of the static configuration directory
+    #[clap(long, default_value = "5")]
+    static_config_poll_secs: u64,
+    /// File (JSON lines) where the audit log of registry changes is persisted
+    #[clap(long)]
+    audit_log_file: Option<String>,
+    /// TOML file mapping API keys to principals. The JWT secret is read from DISCOVERY_JWT_SECRET
+    #[clap(long)]
+    api_keys_file: Option<String>,
+    /// Address of the gRPC API, e.g. 0.0.0.0:4001. The gRPC API is disabled when not set
+    #[clap(long)]
+    grpc_uri: Option<String>,
+    /// Similarity (0 to 1) above which a registered agent or tool is reported as a near-duplicate
+    #[clap(long, default_value = "0.85")]
+    duplicate_threshold: f32,
+    /// Reject near-duplicate registrations instead of only warning about them
+    #[clap(long)]
+    strict_duplicates: bool,
+    /// URL of the evaluation service, polled for the agent scores used to rank agents by reputation
+    #[clap(long)]
+    evaluation_service_url: Option<String>,
+    /// Polling interval, in seconds, of the evaluation service scores
+    #[clap(long, default_value = "60")]
+    reputation_poll_secs: u64,
+    /// Number of attempts of a webhook delivery before it is dead-lettered
+    #[clap(long, default_value = "5")]
+    webhook_max_attempts: u32,
 }
 
 
@@ -59,8 +100,55 @@ async fn main() -> anyhow::Result<()> {
     /************************************************/
     /* Launch Memory Server                         */
     /************************************************/ 
-    let discovery_server=DiscoveryServer::new(args.uri).await?;
-    discovery_server.start_http().await?;
+    let mut auth_config = AuthConfig::default();
+    if let Some(api_keys_file) = args.api_keys_file {
+        auth_config = auth_config.with_api_keys_file(api_keys_file)?;
+    }
+    if let Ok(jwt_secret) = env::var("DISCOVERY_JWT_SECRET") {
+        auth_config = auth_config.with_jwt_secret(jwt_secret);
+    }
+    if !auth_config.is_enabled() {
+        warn!("No API keys nor JWT secret configured: discovery registrations are not authenticated");
+    }
+
+    let config = DiscoveryServerConfig {
+        auth: auth_config,
+        duplicate_detection: DuplicateDetectionConfig {
+            threshold: args.duplicate_threshold,
+            strict: args.strict_duplicates,
+        },
+        webhooks: WebhookConfig {
+            max_attempts: args.webhook_max_attempts.max(1),
+            ..Default::default()
+        },
+    };
+    let discovery_server=DiscoveryServer::with_config(args.uri, config).await?;
+
+    if let Some(audit_log_file) = args.audit_log_file {
+        discovery_server.state.audit.persist_to(&audit_log_file)?;
+    }
+
+    if let Some(seed_file) = args.seed_file {
+        let summary = discovery_server.state.load_snapshot_file(&seed_file)?;
+        info!("Preloaded {} agents, {} tasks and {} tools from {}", summary.agents, summary.tasks, summary.tools, seed_file);
+    }
+
+    if let Some(static_config_dir) = args.static_config_dir {
+        let watcher = StaticConfigWatcher::new(static_config_dir, Duration::from_secs(args.static_config_poll_secs.max(1)));
+        watcher.spawn(discovery_server.state.clone())?;
+    }
+
+    if let Some(evaluation_service_url) = args.evaluation_service_url {
+        let reputation_sync = ReputationSync::new(evaluation_service_url, Duration::from_secs(args.reputation_poll_secs.max(1)));
+        reputation_sync.spawn(discovery_server.state.clone());
+    }
+
+    match args.grpc_uri {
+        Some(grpc_uri) => {
+            tokio::try_join!(discovery_server.start_http(), discovery_server.start_grpc(&grpc_uri))?;
+        }
+        None => discovery_server.start_http().await?,
+    }
 
     /************************************************/
     /* End Launch Memory Server                     */
@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored protoc unless one is provided, so that building does not require a system installation
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    // Generates the gRPC server and client of the discovery service
+    tonic_build::compile_protos("proto/discovery.proto")?;
+    Ok(())
+}
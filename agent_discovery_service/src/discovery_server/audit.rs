@@ -0,0 +1,357 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Query, State},
+    http::{request::Parts, HeaderMap},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::discovery_server::auth::Principal;
+use crate::discovery_server::registry::{ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+audit.rs
+keeps an append-only history of every change made to the registry.
+
+The latest records are held in memory for querying through /admin/audit and, when a file
+is configured, every record is appended to it as JSON lines so that the history survives restarts.
+New records are also broadcast to the subscribers watching the registry.
+
+Only authenticated principals are recorded as callers. The self-declared x-caller-id header
+is kept apart, as the claimed caller, since any client may send it.
+*/
+
+/// Number of records kept in memory, the older ones are only kept in the file.
+const MAX_AUDIT_RECORDS: usize = 10_000;
+
+/// Number of records buffered for subscribers that are slower than the registry changes.
+const CHANGES_CHANNEL_CAPACITY: usize = 1024;
+
+/// Header used by callers to identify themselves, recorded as the claimed caller.
+pub const CALLER_ID_HEADER: &str = "x-caller-id";
+
+/// The kind of change recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Register,
+    Update,
+    Deregister,
+}
+
+/// A top-level field of a definition that differs between two versions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// A single entry of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position of the record in the log, starting at 1.
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub action: AuditAction,
+    pub kind: ResourceKind,
+    pub entity_id: String,
+    /// Authenticated principal that made the change, "anonymous" without credentials.
+    pub caller: String,
+    /// Caller id declared in the x-caller-id header, not verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claimed_caller: Option<String>,
+    /// Network address the change came from, if it came over the network.
+    pub source: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Who and where a change to the registry comes from.
+#[derive(Debug, Clone)]
+pub struct RequestOrigin {
+    pub caller: String,
+    pub claimed_caller: Option<String>,
+    pub source: Option<String>,
+}
+
+impl RequestOrigin {
+    /// Origin of changes made by the server itself (static configuration, seed file, ...).
+    pub fn system(component: &str) -> Self {
+        RequestOrigin {
+            caller: format!("system:{}", component),
+            claimed_caller: None,
+            source: None,
+        }
+    }
+
+    /// Identifies the origin of a request from its headers, authenticated principal and peer address.
+    pub fn resolve(headers: &HeaderMap, principal: Option<&Principal>, source: Option<SocketAddr>) -> Self {
+        // The self-declared caller id is never trusted as the caller
+        let caller = principal
+            .map(|principal| principal.id.clone())
+            .unwrap_or_else(|| Principal::anonymous().id);
+        let claimed_caller = headers
+            .get(CALLER_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+
+        RequestOrigin {
+            caller,
+            claimed_caller,
+            source: source.map(|addr| addr.to_string()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestOrigin {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let source = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+
+        Ok(RequestOrigin::resolve(&parts.headers, parts.extensions.get::<Principal>(), source))
+    }
+}
+
+/// Append-only log of registry changes.
+#[derive(Debug)]
+pub struct AuditLog {
+    /// The latest MAX_AUDIT_RECORDS records.
+    records: RwLock<VecDeque<AuditRecord>>,
+    last_seq: AtomicU64,
+    file: Mutex<Option<File>>,
+    changes: broadcast::Sender<AuditRecord>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        let (changes, _) = broadcast::channel(CHANGES_CHANNEL_CAPACITY);
+        AuditLog {
+            records: RwLock::new(VecDeque::new()),
+            last_seq: AtomicU64::new(0),
+            file: Mutex::new(None),
+            changes,
+        }
+    }
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the records appended from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AuditRecord> {
+        self.changes.subscribe()
+    }
+
+    /// Sequence number of the last record appended to the log.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::SeqCst)
+    }
+
+    /// Persists the log to a JSON lines file, loading the records it already contains.
+    pub fn persist_to(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut loaded = VecDeque::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                loaded.push_back(serde_json::from_str::<AuditRecord>(&line)?);
+                if loaded.len() > MAX_AUDIT_RECORDS {
+                    loaded.pop_front();
+                }
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        let mut records = self.records.write().unwrap();
+        let mut seq = loaded.back().map(|record| record.seq).unwrap_or_default();
+        // Records made before the file was configured are renumbered after the loaded ones
+        for mut record in records.drain(..) {
+            seq += 1;
+            record.seq = seq;
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+            loaded.push_back(record);
+            if loaded.len() > MAX_AUDIT_RECORDS {
+                loaded.pop_front();
+            }
+        }
+        *records = loaded;
+        self.last_seq.store(seq, Ordering::SeqCst);
+        *self.file.lock().unwrap() = Some(file);
+
+        info!("Audit log persisted to {} ({} records)", path.display(), records.len());
+        Ok(())
+    }
+
+    /// Appends a record to the log. Does nothing if neither version of the entry is known.
+    pub fn record(
+        &self,
+        key: &ResourceKey,
+        origin: &RequestOrigin,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) -> Option<AuditRecord> {
+        let action = match (&before, &after) {
+            (None, Some(_)) => AuditAction::Register,
+            (Some(_), Some(_)) => AuditAction::Update,
+            (Some(_), None) => AuditAction::Deregister,
+            (None, None) => return None,
+        };
+
+        let changes = diff_fields(before.as_ref(), after.as_ref());
+
+        let mut records = self.records.write().unwrap();
+        let record = AuditRecord {
+            seq: self.last_seq.fetch_add(1, Ordering::SeqCst) + 1,
+            timestamp: Utc::now(),
+            action,
+            kind: key.kind,
+            entity_id: key.id.clone(),
+            caller: origin.caller.clone(),
+            claimed_caller: origin.claimed_caller.clone(),
+            source: origin.source.clone(),
+            before,
+            after,
+            changes,
+        };
+
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let written = serde_json::to_string(&record)
+                .map_err(anyhow::Error::from)
+                .and_then(|line| writeln!(file, "{}", line).map_err(anyhow::Error::from));
+            if let Err(e) = written {
+                warn!("Failed to append audit record {} to file: {}", record.seq, e);
+            }
+        }
+
+        if records.len() == MAX_AUDIT_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+        // Sending only fails when nobody is watching the registry
+        let _ = self.changes.send(record.clone());
+        Some(record)
+    }
+
+    /// Sequence number of the oldest record kept in memory, None if the log is empty.
+    pub fn first_seq(&self) -> Option<u64> {
+        self.records.read().unwrap().front().map(|record| record.seq)
+    }
+
+    /// Returns the records kept in memory appended after the given sequence number, oldest first.
+    pub fn records_after(&self, seq: u64) -> Vec<AuditRecord> {
+        let records = self.records.read().unwrap();
+        records.iter().filter(|record| record.seq > seq).cloned().collect()
+    }
+
+    /// Returns the records kept in memory matching the query, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditRecord> {
+        let records = self.records.read().unwrap();
+        let matching = records.iter().filter(|record| {
+            query.entity_id.as_ref().is_none_or(|id| &record.entity_id == id)
+                && query.kind.is_none_or(|kind| record.kind == kind)
+                && query.caller.as_ref().is_none_or(|caller| &record.caller == caller)
+                && query.since.is_none_or(|since| record.timestamp >= since)
+                && query.until.is_none_or(|until| record.timestamp <= until)
+        });
+
+        match query.limit {
+            // Keep the most recent records when a limit is given
+            Some(limit) => {
+                let mut latest: Vec<AuditRecord> = matching.rev().take(limit).cloned().collect();
+                latest.reverse();
+                latest
+            }
+            None => matching.cloned().collect(),
+        }
+    }
+}
+
+/// Computes the top-level fields that differ between two versions of a definition.
+fn diff_fields(before: Option<&serde_json::Value>, after: Option<&serde_json::Value>) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let before_fields = before.and_then(|v| v.as_object()).unwrap_or(&empty);
+    let after_fields = after.and_then(|v| v.as_object()).unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = before_fields.keys().chain(after_fields.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| before_fields.get(*field) != after_fields.get(*field))
+        .map(|field| FieldChange {
+            field: field.clone(),
+            before: before_fields.get(field).cloned(),
+            after: after_fields.get(field).cloned(),
+        })
+        .collect()
+}
+
+/// Filters accepted by /admin/audit, e.g. /admin/audit?entity_id=agent_1&since=2025-01-01T00:00:00Z
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ResourceKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl AppState {
+    /// Records a change of a registry entry in the audit log.
+    pub fn audit_change<T: Serialize>(
+        &self,
+        key: &ResourceKey,
+        origin: &RequestOrigin,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Option<AuditRecord> {
+        let before = before.and_then(|def| serde_json::to_value(def).ok());
+        let after = after.and_then(|def| serde_json::to_value(def).ok());
+        self.audit.record(key, origin, before, after)
+    }
+}
+
+/// Lists audit records, filtered by entity id, kind, caller and time range.
+pub async fn list_audit_records(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Json<Vec<AuditRecord>> {
+    info!("Received audit query: {:?}", query);
+    Json(state.audit.query(&query))
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use axum::{
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use dashmap::mapref::entry::Entry;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::discovery_server::registry::ResourceKey;
+use crate::discovery_server::server::AppState;
+
+/*
+auth.rs
+authenticates the callers of the mutating discovery routes and enforces ownership of registry entries.
+
+Two kinds of credentials are accepted:
+* an API key, sent in the x-api-key header, mapped to a principal by the server configuration
+* a JWT signed with the shared HS256 secret, sent as "Authorization: Bearer <token>"
+
+When no credentials are configured, authentication is disabled and every caller is
+treated as an anonymous administrator, which preserves the behaviour of an open registry.
+*/
+
+/// Header carrying an API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Role allowing to modify or deregister entries owned by other principals.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// An authenticated caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Principal {
+    pub id: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Principal {
+    /// The principal used when authentication is disabled.
+    pub fn anonymous() -> Self {
+        Principal {
+            id: "anonymous".to_string(),
+            roles: vec![ADMIN_ROLE.to_string()],
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.roles.iter().any(|role| role == ADMIN_ROLE)
+    }
+}
+
+/// The principal owning a registry entry, as exported in the snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedEntry {
+    #[serde(flatten)]
+    pub key: ResourceKey,
+    pub owner: String,
+}
+
+/// Claims expected in the JWT bearer tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Subject, used as principal id.
+    pub sub: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Expiration time (seconds since epoch).
+    pub exp: u64,
+}
+
+/// An API key entry of the keys file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyEntry {
+    pub key: String,
+    pub principal: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Layout of the API keys file (TOML):
+///
+/// [[keys]]
+/// key = "secret-key"
+/// principal = "planner-agent"
+/// roles = ["admin"]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiKeysFile {
+    #[serde(default)]
+    pub keys: Vec<ApiKeyEntry>,
+}
+
+/// Authentication settings of the discovery server.
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    /// Key: API key, Value: the principal it authenticates.
+    pub api_keys: HashMap<String, Principal>,
+    /// Shared secret used to validate HS256 JWT bearer tokens.
+    pub jwt_secret: Option<String>,
+}
+
+impl AuthConfig {
+    /// Loads the API keys from a TOML file.
+    pub fn with_api_keys_file(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let keys_file: ApiKeysFile = toml::from_str(&content)?;
+        for entry in keys_file.keys {
+            self.api_keys.insert(
+                entry.key,
+                Principal {
+                    id: entry.principal,
+                    roles: entry.roles,
+                },
+            );
+        }
+        Ok(self)
+    }
+
+    pub fn with_jwt_secret(mut self, jwt_secret: impl Into<String>) -> Self {
+        self.jwt_secret = Some(jwt_secret.into());
+        self
+    }
+
+    /// Authentication is enabled as soon as an API key or a JWT secret is configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt_secret.is_some()
+    }
+
+    /// Identifies the caller from the request headers.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, &'static str> {
+        if !self.is_enabled() {
+            return Ok(Principal::anonymous());
+        }
+
+        if let Some(api_key) = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+            return self.api_keys.get(api_key).cloned().ok_or("Invalid API key");
+        }
+
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match (bearer, &self.jwt_secret) {
+            (Some(token), Some(secret)) => {
+                let token_data = decode::<Claims>(
+                    token.trim(),
+                    &DecodingKey::from_secret(secret.as_bytes()),
+                    &Validation::new(Algorithm::HS256),
+                )
+                .map_err(|e| {
+                    warn!("Rejected bearer token: {}", e);
+                    "Invalid bearer token"
+                })?;
+                Ok(Principal {
+                    id: token_data.claims.sub,
+                    roles: token_data.claims.roles,
+                })
+            }
+            (Some(_), None) => Err("Bearer tokens are not accepted by this server"),
+            (None, _) => Err("Missing credentials"),
+        }
+    }
+}
+
+/// Middleware authenticating the caller and making the Principal available to the handlers.
+pub async fn require_auth(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    match state.auth.authenticate(request.headers()) {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Err(reason) => {
+            info!("Rejected unauthenticated request to {}: {}", request.uri(), reason);
+            (StatusCode::UNAUTHORIZED, reason).into_response()
+        }
+    }
+}
+
+/// Middleware restricting a route to principals holding the admin role.
+/// Must be layered after require_auth.
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    match request.extensions().get::<Principal>() {
+        Some(principal) if principal.is_admin() => next.run(request).await,
+        _ => (StatusCode::FORBIDDEN, "Admin role required").into_response(),
+    }
+}
+
+impl AppState {
+    /// Checks that the principal may modify or deregister the entry:
+    /// unowned entries and entries it owns are allowed, as well as any entry for admins.
+    pub fn check_ownership(&self, key: &ResourceKey, principal: &Principal) -> Result<(), (StatusCode, &'static str)> {
+        match self.owners.get(key) {
+            Some(owner) if owner.value() != &principal.id && !principal.is_admin() => {
+                Err((StatusCode::FORBIDDEN, "Entry is owned by another principal"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the principal may register the entry and, if the entry is unowned, records it as the owner.
+    /// The check and the claim are atomic, so that two principals registering the same new entry cannot both own it.
+    /// Returns true if the principal became the owner.
+    pub fn claim_ownership(&self, key: &ResourceKey, principal: &Principal) -> Result<bool, (StatusCode, &'static str)> {
+        match self.owners.entry(key.clone()) {
+            Entry::Occupied(owner) if owner.get() != &principal.id && !principal.is_admin() => {
+                Err((StatusCode::FORBIDDEN, "Entry is owned by another principal"))
+            }
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(vacant) => {
+                vacant.insert(principal.id.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    /// Gives up a claim made by claim_ownership, when the registration is finally rejected.
+    pub fn release_ownership(&self, key: &ResourceKey, principal: &Principal) {
+        self.owners.remove_if(key, |_, owner| owner == &principal.id);
+    }
+
+    /// Lists the owner of every owned entry, sorted by entry.
+    pub fn owned_entries(&self) -> Vec<OwnedEntry> {
+        let mut entries: Vec<OwnedEntry> = self
+            .owners
+            .iter()
+            .map(|e| OwnedEntry {
+                key: e.key().clone(),
+                owner: e.value().clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+}
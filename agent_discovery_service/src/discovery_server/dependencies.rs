@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::discovery_server::operations::Rejection;
+use crate::discovery_server::registry::{RegistryResponse, ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+dependencies.rs
+tracks the dependencies declared between registry entries (a task needing a tool,
+an agent delegating to another agent, ...).
+
+Dependencies are declared at registration time through the depends_on field and are
+used to warn about, or reject, deregistrations that would orphan dependent entries.
+A registration without depends_on keeps the dependencies declared before.
+*/
+
+/// A dependency between two registry entries: `from` depends on `to`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    pub from: ResourceKey,
+    pub to: ResourceKey,
+}
+
+/// A node of the dependency graph.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    #[serde(flatten)]
+    pub key: ResourceKey,
+    pub name: Option<String>,
+    /// True when the entry is referenced by a dependency but is not registered.
+    pub missing: bool,
+}
+
+/// The dependency graph of the registry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+impl DependencyGraph {
+    /// Renders the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph registry {\n");
+        for node in &self.nodes {
+            let shape = match node.key.kind {
+                ResourceKind::Agent => "ellipse",
+                ResourceKind::Task => "box",
+                ResourceKind::Tool => "component",
+            };
+            let style = if node.missing { ", style=dashed, color=red" } else { "" };
+            let label = node.name.as_deref().unwrap_or(&node.key.id);
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\", shape={}{}];\n",
+                escape_dot(&node.key.to_string()),
+                escape_dot(label),
+                shape,
+                style
+            ));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\";\n",
+                escape_dot(&edge.from.to_string()),
+                escape_dot(&edge.to.to_string())
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Query parameters of the deregistration routes, e.g. /tools/deregister?force=true
+#[derive(Debug, Default, Deserialize)]
+pub struct DeregisterParams {
+    /// Deregister even if other entries depend on this one.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Output formats of the graph endpoint.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Json,
+    Dot,
+}
+
+/// Query parameters of the graph endpoint, e.g. /graph?format=dot
+#[derive(Debug, Default, Deserialize)]
+pub struct GraphParams {
+    #[serde(default)]
+    pub format: GraphFormat,
+}
+
+impl AppState {
+    /// Returns true if an entry with this key is registered.
+    pub fn contains(&self, key: &ResourceKey) -> bool {
+        match key.kind {
+            ResourceKind::Agent => self.db_agents.contains_key(&key.id),
+            ResourceKind::Task => self.db_tasks.contains_key(&key.id),
+            ResourceKind::Tool => self.db_tools.contains_key(&key.id),
+        }
+    }
+
+    /// Returns the name of a registered entry.
+    pub fn entry_name(&self, key: &ResourceKey) -> Option<String> {
+        match key.kind {
+            ResourceKind::Agent => self.db_agents.get(&key.id).map(|e| e.value().name.clone()),
+            ResourceKind::Task => self.db_tasks.get(&key.id).map(|e| e.value().name.clone()),
+            ResourceKind::Tool => self.db_tools.get(&key.id).map(|e| e.value().name.clone()),
+        }
+    }
+
+    /// Resolves a dependency reference, either "kind:id" or a bare id looked up among
+    /// the registered agents, tools and tasks (in this order).
+    pub fn resolve_reference(&self, reference: &str) -> Option<ResourceKey> {
+        if let Some((kind, id)) = reference.split_once(':') {
+            if let Ok(kind) = kind.parse::<ResourceKind>() {
+                return Some(ResourceKey::new(kind, id));
+            }
+        }
+
+        [ResourceKind::Agent, ResourceKind::Tool, ResourceKind::Task]
+            .into_iter()
+            .map(|kind| ResourceKey::new(kind, reference))
+            .find(|key| self.contains(key))
+    }
+
+    /// Records the dependencies of an entry, replacing the previous ones.
+    /// Returns warnings for the references that do not match a registered entry.
+    pub fn set_dependencies(&self, key: &ResourceKey, references: &[String]) -> Vec<String> {
+        // A deregistration checking the dependents cannot interleave with the update
+        let _graph = self.dependency_lock.lock().unwrap();
+        let mut warnings = Vec::new();
+        let mut resolved = BTreeSet::new();
+
+        for reference in references {
+            match self.resolve_reference(reference) {
+                Some(dependency) if &dependency == key => {}
+                Some(dependency) => {
+                    if !self.contains(&dependency) {
+                        warnings.push(format!("{} depends on {} which is not registered", key, dependency));
+                    }
+                    resolved.insert(dependency);
+                }
+                None => warnings.push(format!("{} depends on unknown entry '{}'", key, reference)),
+            }
+        }
+
+        if resolved.is_empty() {
+            self.dependencies.remove(key);
+        } else {
+            self.dependencies.insert(key.clone(), resolved);
+        }
+        warnings
+    }
+
+    /// Returns the entries that depend on the given one.
+    pub fn dependents_of(&self, key: &ResourceKey) -> Vec<ResourceKey> {
+        let mut dependents: Vec<ResourceKey> = self
+            .dependencies
+            .iter()
+            .filter(|e| e.value().contains(key))
+            .map(|e| e.key().clone())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Checks whether an entry can be deregistered without orphaning its dependents.
+    /// Callers hold dependency_lock until the entry is removed, so that no dependent registers in between.
+    /// Without force, the deregistration is rejected with 409 Conflict; with force,
+    /// it is allowed and the orphaned dependents are returned as warnings.
+    pub fn check_deregistration(&self, key: &ResourceKey, force: bool) -> Result<Vec<String>, Rejection> {
+        let dependents = self.dependents_of(key);
+        if dependents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let warnings = dependents
+            .iter()
+            .map(|dependent| format!("{} depends on {}", dependent, key))
+            .collect::<Vec<String>>();
+
+        if force {
+            Ok(warnings)
+        } else {
+            Err((
+                StatusCode::CONFLICT,
+                RegistryResponse::new(format!("{} has dependents, use force=true to deregister it anyway", key)).with_warnings(warnings),
+            ))
+        }
+    }
+
+    /// Lists every dependency edge of the registry.
+    pub fn dependency_edges(&self) -> Vec<DependencyEdge> {
+        let mut edges: Vec<DependencyEdge> = self
+            .dependencies
+            .iter()
+            .flat_map(|e| {
+                let from = e.key().clone();
+                e.value()
+                    .iter()
+                    .map(|to| DependencyEdge { from: from.clone(), to: to.clone() })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    /// Builds the dependency graph: every registered entry and every dependency edge.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let edges = self.dependency_edges();
+
+        let mut keys: BTreeSet<ResourceKey> = BTreeSet::new();
+        keys.extend(self.db_agents.iter().map(|e| ResourceKey::agent(e.key().as_str())));
+        keys.extend(self.db_tasks.iter().map(|e| ResourceKey::task(e.key().as_str())));
+        keys.extend(self.db_tools.iter().map(|e| ResourceKey::tool(e.key().as_str())));
+        for edge in &edges {
+            keys.insert(edge.from.clone());
+            keys.insert(edge.to.clone());
+        }
+
+        let names: BTreeMap<ResourceKey, Option<String>> = keys
+            .into_iter()
+            .map(|key| {
+                let name = self.entry_name(&key);
+                (key, name)
+            })
+            .collect();
+
+        let nodes = names
+            .into_iter()
+            .map(|(key, name)| GraphNode {
+                missing: name.is_none(),
+                key,
+                name,
+            })
+            .collect();
+
+        DependencyGraph { nodes, edges }
+    }
+}
+
+/// Returns the dependency graph of the registry, as JSON (default) or DOT.
+pub async fn get_dependency_graph(State(state): State<AppState>, Query(params): Query<GraphParams>) -> Response {
+    info!("Received dependency graph request with format: {:?}", params.format);
+
+    let graph = state.dependency_graph();
+    match params.format {
+        GraphFormat::Json => Json(graph).into_response(),
+        GraphFormat::Dot => ([(header::CONTENT_TYPE, "text/vnd.graphviz")], graph.to_dot()).into_response(),
+    }
+}
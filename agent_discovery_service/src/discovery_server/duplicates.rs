@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use agent_models::registry::registry_models::{AgentDefinition, ToolDefinition};
+
+use crate::discovery_server::registry::ResourceKey;
+use crate::discovery_server::server::AppState;
+use crate::embeddings::similarity_search::{generate_embedding, tokenize, VectorDB};
+
+/*
+duplicates.rs
+detects agents and tools registered under a new id while doing the same thing as an existing entry.
+
+The similarity of two entries averages:
+* the overlap (Jaccard index) of their skills for agents, of the words of their name and description for tools
+* the cosine similarity between the embeddings of their names, descriptions and skills
+Entries above the threshold are reported as warnings, or rejected in strict mode; updates of
+an entry already registered are never rejected, their near-duplicates stay warnings.
+*/
+
+/// Default similarity above which two entries are considered near-duplicates.
+pub const DEFAULT_DUPLICATE_THRESHOLD: f32 = 0.85;
+
+/// Settings of the near-duplicate detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateDetectionConfig {
+    /// Similarity, between 0 and 1, above which two entries are considered near-duplicates.
+    pub threshold: f32,
+    /// Reject the registrations of near-duplicates instead of only warning about them.
+    pub strict: bool,
+}
+
+impl Default for DuplicateDetectionConfig {
+    fn default() -> Self {
+        DuplicateDetectionConfig {
+            threshold: DEFAULT_DUPLICATE_THRESHOLD,
+            strict: false,
+        }
+    }
+}
+
+/// An existing entry close to the one being registered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearDuplicate {
+    pub key: ResourceKey,
+    pub name: String,
+    /// Combined similarity, between 0 and 1.
+    pub similarity: f32,
+    pub overlap: f32,
+    pub semantic_similarity: f32,
+}
+
+impl NearDuplicate {
+    pub fn describe(&self, registered: &ResourceKey) -> String {
+        format!(
+            "{} looks like a near-duplicate of {} '{}' (similarity {:.2}: overlap {:.2}, semantic {:.2})",
+            registered, self.key, self.name, self.similarity, self.overlap, self.semantic_similarity
+        )
+    }
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+/// What two entries are compared on.
+struct Features {
+    key: ResourceKey,
+    name: String,
+    /// Skills of an agent, words of a tool.
+    terms: HashSet<String>,
+    /// Text embedded for the semantic similarity.
+    text: String,
+}
+
+fn agent_features(agent_def: &AgentDefinition) -> Features {
+    let skill_names = agent_def.skills.iter().map(|skill| skill.name.as_str()).collect::<Vec<&str>>().join(" ");
+    Features {
+        key: ResourceKey::agent(agent_def.id.as_str()),
+        name: agent_def.name.clone(),
+        terms: agent_def.skills.iter().map(|skill| skill.name.to_lowercase()).collect(),
+        text: format!("{} {} {}", agent_def.name, agent_def.description, skill_names),
+    }
+}
+
+fn tool_features(tool_def: &ToolDefinition) -> Features {
+    let text = format!("{} {}", tool_def.name, tool_def.description);
+    Features {
+        key: ResourceKey::tool(tool_def.id.as_str()),
+        name: tool_def.name.clone(),
+        terms: tokenize(&text).into_iter().collect(),
+        text,
+    }
+}
+
+/// Scores the candidates against the entry being registered, keeping those above the threshold.
+fn near_duplicates(registered: &Features, candidates: Vec<Features>, threshold: f32) -> Vec<NearDuplicate> {
+    let embedding = generate_embedding(&registered.text);
+
+    let mut duplicates: Vec<NearDuplicate> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.key != registered.key)
+        .map(|candidate| {
+            let overlap = jaccard(&registered.terms, &candidate.terms);
+            let semantic_similarity = VectorDB::cosine_similarity(&embedding, &generate_embedding(&candidate.text)).max(0.0);
+            NearDuplicate {
+                key: candidate.key,
+                name: candidate.name,
+                similarity: (overlap + semantic_similarity) / 2.0,
+                overlap,
+                semantic_similarity,
+            }
+        })
+        .filter(|duplicate| duplicate.similarity >= threshold)
+        .collect();
+    duplicates.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+    duplicates
+}
+
+impl AppState {
+    /// Returns the registered agents, other than the given one, doing the same thing.
+    pub fn near_duplicate_agents(&self, agent_def: &AgentDefinition) -> Vec<NearDuplicate> {
+        let candidates = self.db_agents.iter().map(|e| agent_features(e.value())).collect();
+        near_duplicates(&agent_features(agent_def), candidates, self.duplicate_detection.threshold)
+    }
+
+    /// Returns the registered tools, other than the given one, doing the same thing.
+    pub fn near_duplicate_tools(&self, tool_def: &ToolDefinition) -> Vec<NearDuplicate> {
+        let candidates = self.db_tools.iter().map(|e| tool_features(e.value())).collect();
+        near_duplicates(&tool_features(tool_def), candidates, self.duplicate_detection.threshold)
+    }
+}
@@ -0,0 +1,58 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/*
+etag.rs
+conditional GET support for the list routes.
+
+The ETag of a response is a digest of its JSON body, so it changes with any registry change
+visible in the response (definitions, labels filtering them, ...). The lists are sorted by id
+beforehand, so that the same registry content always gives the same tag.
+Clients caching the lists revalidate them with If-None-Match and get a bodyless
+304 Not Modified while the registry did not change.
+*/
+
+/// Computes the entity tag of a JSON body.
+pub fn entity_tag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// Returns true if the If-None-Match header lists the entity tag (or is a wildcard).
+fn matches_if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Serializes a value as a JSON response carrying its ETag,
+/// or answers 304 Not Modified if the client already has this version.
+pub fn conditional_json<T: Serialize>(headers: &HeaderMap, value: &T) -> Result<Response, (StatusCode, String)> {
+    let body = serde_json::to_vec(value)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to serialize the response: {}", e)))?;
+    let etag = entity_tag(&body);
+    let etag_header = HeaderValue::from_str(&etag).expect("entity tags are hex digests");
+
+    if matches_if_none_match(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag_header)]).into_response());
+    }
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::ETAG, etag_header),
+            (header::CONTENT_TYPE, HeaderValue::from_static("application/json")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
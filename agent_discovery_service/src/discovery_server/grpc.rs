@@ -0,0 +1,391 @@
+use std::net::SocketAddr;
+
+use axum::http::StatusCode;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::audit::{AuditAction, AuditRecord, RequestOrigin};
+use crate::discovery_server::auth::Principal;
+use crate::discovery_server::labels::{parse_selector, LabelSelector, Labels};
+use crate::discovery_server::operations::{Rejection, RegistryEntry};
+use crate::discovery_server::registry::{Registration, RegistryResponse, ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+grpc.rs
+exposes the registry through gRPC, alongside the HTTP routes and sharing the same AppState.
+
+Registrations go through the same operations as the HTTP routes (authentication,
+ownership, audit, labels and dependencies), and the Watch RPC streams the changes
+recorded in the audit log. As the audit log tells who changed what, watching is
+restricted to admins, like /admin/audit.
+*/
+
+pub mod proto {
+    tonic::include_proto!("swarm.discovery.v1");
+}
+
+use proto::registry_service_server::{RegistryService, RegistryServiceServer};
+
+/// Default number of candidates returned by FindTaskCandidates.
+const DEFAULT_CANDIDATES_LIMIT: usize = 10;
+
+/// Number of events buffered for each watcher.
+const WATCH_CHANNEL_CAPACITY: usize = 256;
+
+/// gRPC implementation of the registry service.
+#[derive(Clone)]
+pub struct GrpcRegistryService {
+    state: AppState,
+}
+
+impl GrpcRegistryService {
+    pub fn new(state: AppState) -> Self {
+        GrpcRegistryService { state }
+    }
+
+    pub fn into_server(self) -> RegistryServiceServer<Self> {
+        RegistryServiceServer::new(self)
+    }
+
+    /// Authenticates the caller from the request metadata, like the HTTP require_auth middleware.
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<(Principal, RequestOrigin), Status> {
+        let headers = request.metadata().clone().into_headers();
+        let principal = self.state.auth.authenticate(&headers).map_err(|reason| {
+            info!("Rejected unauthenticated gRPC request: {}", reason);
+            Status::unauthenticated(reason)
+        })?;
+        let origin = RequestOrigin::resolve(&headers, Some(&principal), request.remote_addr());
+        Ok((principal, origin))
+    }
+
+    /// Authenticates the caller and checks that it holds the admin role, like the HTTP require_admin middleware.
+    fn authenticate_admin<T>(&self, request: &Request<T>) -> Result<Principal, Status> {
+        let (principal, _) = self.authenticate(request)?;
+        if !principal.is_admin() {
+            return Err(Status::permission_denied("Admin role required"));
+        }
+        Ok(principal)
+    }
+
+    fn register_entry<T: RegistryEntry + serde::de::DeserializeOwned>(
+        &self,
+        request: proto::RegisterRequest,
+        principal: &Principal,
+        origin: &RequestOrigin,
+    ) -> Result<RegistryResponse, Status> {
+        let definition: T = serde_json::from_str(&request.definition_json)
+            .map_err(|e| Status::invalid_argument(format!("Invalid {} definition: {}", T::KIND, e)))?;
+        let registration = Registration {
+            definition,
+            // Repeated fields cannot be told missing from empty, clearing is explicit
+            depends_on: Some(request.depends_on).filter(|depends_on| !depends_on.is_empty() || request.clear_dependencies),
+            labels: Some(request.labels.into_iter().collect::<Labels>())
+                .filter(|labels| !labels.is_empty() || request.clear_labels),
+        };
+        self.state.register(registration, request.strict, principal, origin).map_err(rejection_status)
+    }
+}
+
+#[tonic::async_trait]
+impl RegistryService for GrpcRegistryService {
+    async fn register(&self, request: Request<proto::RegisterRequest>) -> Result<Response<proto::RegistryReply>, Status> {
+        let (principal, origin) = self.authenticate(&request)?;
+        let request = request.into_inner();
+        let kind = resource_kind(request.kind)?;
+        info!("Received gRPC register request for {}", kind);
+
+        let response = match kind {
+            ResourceKind::Agent => self.register_entry::<AgentDefinition>(request, &principal, &origin)?,
+            ResourceKind::Task => self.register_entry::<TaskDefinition>(request, &principal, &origin)?,
+            ResourceKind::Tool => self.register_entry::<ToolDefinition>(request, &principal, &origin)?,
+        };
+        Ok(Response::new(reply(response)))
+    }
+
+    async fn deregister(&self, request: Request<proto::DeregisterRequest>) -> Result<Response<proto::RegistryReply>, Status> {
+        let (principal, origin) = self.authenticate(&request)?;
+        let request = request.into_inner();
+        let kind = resource_kind(request.kind)?;
+        info!("Received gRPC deregister request for {}:{}", kind, request.id);
+
+        let outcome = match kind {
+            ResourceKind::Agent => self.state.deregister::<AgentDefinition>(&request.id, request.force, &principal, &origin),
+            ResourceKind::Task => self.state.deregister::<TaskDefinition>(&request.id, request.force, &principal, &origin),
+            ResourceKind::Tool => self.state.deregister::<ToolDefinition>(&request.id, request.force, &principal, &origin),
+        };
+        outcome.map(|response| Response::new(reply(response))).map_err(rejection_status)
+    }
+
+    async fn get_entry(&self, request: Request<proto::GetEntryRequest>) -> Result<Response<proto::Entry>, Status> {
+        let request = request.into_inner();
+        let key = ResourceKey::new(resource_kind(request.kind)?, request.id);
+        info!("Received gRPC get request for {}", key);
+
+        self.state
+            .grpc_entry(&key)
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("{} is not registered", key)))
+    }
+
+    async fn list_entries(&self, request: Request<proto::ListEntriesRequest>) -> Result<Response<proto::ListEntriesResponse>, Status> {
+        let request = request.into_inner();
+        let kind = resource_kind(request.kind)?;
+        let selector = grpc_selector(&request.selector)?;
+        info!("Received gRPC list request for {} with selector: {}", kind, selector);
+
+        let entries = self
+            .state
+            .select_ids(kind, &selector)
+            .into_iter()
+            .filter_map(|id| self.state.grpc_entry(&ResourceKey::new(kind, id)))
+            .collect();
+        Ok(Response::new(proto::ListEntriesResponse { entries }))
+    }
+
+    async fn search_by_skill(&self, request: Request<proto::SearchBySkillRequest>) -> Result<Response<proto::ListEntriesResponse>, Status> {
+        let request = request.into_inner();
+        if request.skill.is_empty() {
+            return Err(Status::invalid_argument("skill is missing"));
+        }
+        let selector = grpc_selector(&request.selector)?;
+        info!("Received gRPC search request for skill: {}", request.skill);
+
+        let entries = self
+            .state
+            .agents_with_skill(&request.skill, &selector)
+            .into_iter()
+            .filter_map(|agent_def| self.state.grpc_entry(&ResourceKey::agent(agent_def.id)))
+            .collect();
+        Ok(Response::new(proto::ListEntriesResponse { entries }))
+    }
+
+    async fn find_task_candidates(
+        &self,
+        request: Request<proto::FindTaskCandidatesRequest>,
+    ) -> Result<Response<proto::FindTaskCandidatesResponse>, Status> {
+        let request = request.into_inner();
+        info!("Received gRPC candidates request for task: {}", request.task_id);
+
+        let task_def = self
+            .state
+            .db_tasks
+            .get(&request.task_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| Status::not_found(format!("task '{}' is not registered", request.task_id)))?;
+        let limit = match request.limit {
+            0 => DEFAULT_CANDIDATES_LIMIT,
+            limit => limit as usize,
+        };
+
+        let candidates = self
+            .state
+            .match_task_candidates(&task_def, limit)
+            .into_iter()
+            .map(|candidate| proto::TaskCandidate {
+                kind: proto_kind(candidate.kind) as i32,
+                id: candidate.id,
+                name: candidate.name,
+                score: candidate.score,
+                lexical_coverage: candidate.lexical_coverage,
+                semantic_similarity: candidate.semantic_similarity,
+                matched_skills: candidate.matched_skills,
+            })
+            .collect();
+        Ok(Response::new(proto::FindTaskCandidatesResponse { candidates }))
+    }
+
+    type WatchStream = ReceiverStream<Result<proto::RegistryEvent, Status>>;
+
+    async fn watch(&self, request: Request<proto::WatchRequest>) -> Result<Response<Self::WatchStream>, Status> {
+        let principal = self.authenticate_admin(&request)?;
+        let request = request.into_inner();
+        let filter = WatchFilter {
+            kinds: request.kinds.into_iter().map(resource_kind).collect::<Result<Vec<_>, _>>()?,
+            entity_id: Some(request.entity_id).filter(|id| !id.is_empty()),
+        };
+        info!("Received gRPC watch request from {} from seq {}", principal.id, request.from_seq);
+
+        // Subscribe before replaying so that no change falls between the replay and the live stream
+        let mut changes = self.state.audit.subscribe();
+        // Only the latest records are kept in memory, older ones cannot be replayed
+        if let Some(first_seq) = self.state.audit.first_seq() {
+            if request.from_seq > 0 && request.from_seq + 1 < first_seq {
+                return Err(Status::out_of_range(format!(
+                    "Changes before seq {} are no longer available, watch again from seq {}",
+                    first_seq,
+                    first_seq - 1
+                )));
+            }
+        }
+        let replay = if request.from_seq > 0 {
+            self.state.audit.records_after(request.from_seq)
+        } else {
+            Vec::new()
+        };
+
+        let (tx, rx) = mpsc::channel(WATCH_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let mut last_seq = request.from_seq;
+            for record in replay {
+                last_seq = record.seq;
+                if filter.matches(&record) && tx.send(Ok(registry_event(record))).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                match changes.recv().await {
+                    // Records already replayed are skipped
+                    Ok(record) if record.seq <= last_seq => {}
+                    Ok(record) => {
+                        last_seq = record.seq;
+                        if filter.matches(&record) && tx.send(Ok(registry_event(record))).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("gRPC watcher lagged behind the registry changes, {} events lost", skipped);
+                        let status = Status::data_loss(format!(
+                            "{} events lost, watch again from seq {} to resume",
+                            skipped, last_seq
+                        ));
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Which changes a watcher is interested in.
+struct WatchFilter {
+    kinds: Vec<ResourceKind>,
+    entity_id: Option<String>,
+}
+
+impl WatchFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&record.kind))
+            && self.entity_id.as_ref().is_none_or(|id| &record.entity_id == id)
+    }
+}
+
+impl AppState {
+    /// Builds the gRPC representation of a registered entry.
+    pub fn grpc_entry(&self, key: &ResourceKey) -> Option<proto::Entry> {
+        let (name, description, skills, definition_json) = match key.kind {
+            ResourceKind::Agent => {
+                let agent_def = AgentDefinition::get(self, &key.id)?;
+                let skills = agent_def.skills.iter().map(|skill| skill.name.clone()).collect();
+                (agent_def.name.clone(), agent_def.description.clone(), skills, serde_json::to_string(&agent_def))
+            }
+            ResourceKind::Task => {
+                let task_def = TaskDefinition::get(self, &key.id)?;
+                (task_def.name.clone(), task_def.description.clone(), Vec::new(), serde_json::to_string(&task_def))
+            }
+            ResourceKind::Tool => {
+                let tool_def = ToolDefinition::get(self, &key.id)?;
+                (tool_def.name.clone(), tool_def.description.clone(), Vec::new(), serde_json::to_string(&tool_def))
+            }
+        };
+
+        let depends_on = self
+            .dependencies
+            .get(key)
+            .map(|e| e.value().iter().map(|dependency| dependency.to_string()).collect())
+            .unwrap_or_default();
+
+        Some(proto::Entry {
+            kind: proto_kind(key.kind) as i32,
+            id: key.id.clone(),
+            name,
+            description,
+            skills,
+            labels: self.labels_of(key).into_iter().collect(),
+            depends_on,
+            definition_json: definition_json.unwrap_or_default(),
+        })
+    }
+}
+
+/// Serves the gRPC API on the given address until the server fails.
+pub async fn serve_grpc(state: AppState, addr: SocketAddr) -> anyhow::Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(GrpcRegistryService::new(state).into_server())
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+fn resource_kind(kind: i32) -> Result<ResourceKind, Status> {
+    match proto::ResourceKind::try_from(kind) {
+        Ok(proto::ResourceKind::Agent) => Ok(ResourceKind::Agent),
+        Ok(proto::ResourceKind::Task) => Ok(ResourceKind::Task),
+        Ok(proto::ResourceKind::Tool) => Ok(ResourceKind::Tool),
+        _ => Err(Status::invalid_argument(format!("Unknown resource kind: {}", kind))),
+    }
+}
+
+fn proto_kind(kind: ResourceKind) -> proto::ResourceKind {
+    match kind {
+        ResourceKind::Agent => proto::ResourceKind::Agent,
+        ResourceKind::Task => proto::ResourceKind::Task,
+        ResourceKind::Tool => proto::ResourceKind::Tool,
+    }
+}
+
+fn grpc_selector(selector: &str) -> Result<LabelSelector, Status> {
+    parse_selector(Some(selector).filter(|s| !s.is_empty())).map_err(|(_, message)| Status::invalid_argument(message))
+}
+
+fn reply(response: RegistryResponse) -> proto::RegistryReply {
+    proto::RegistryReply {
+        message: response.message,
+        warnings: response.warnings,
+    }
+}
+
+/// Maps a rejected registry operation to the equivalent gRPC status.
+fn rejection_status((status, response): Rejection) -> Status {
+    let message = if response.warnings.is_empty() {
+        response.message
+    } else {
+        format!("{} ({})", response.message, response.warnings.join("; "))
+    };
+    match status {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::failed_precondition(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn registry_event(record: AuditRecord) -> proto::RegistryEvent {
+    let action = match record.action {
+        AuditAction::Register => proto::ChangeAction::Register,
+        AuditAction::Update => proto::ChangeAction::Update,
+        AuditAction::Deregister => proto::ChangeAction::Deregister,
+    };
+    let entry = record.after.as_ref().or(record.before.as_ref());
+
+    proto::RegistryEvent {
+        seq: record.seq,
+        timestamp: record.timestamp.to_rfc3339(),
+        action: action as i32,
+        kind: proto_kind(record.kind) as i32,
+        entity_id: record.entity_id,
+        caller: record.caller,
+        entry_json: entry.map(|value| value.to_string()).unwrap_or_default(),
+    }
+}
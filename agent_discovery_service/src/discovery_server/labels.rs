@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::registry::{ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+labels.rs
+attaches arbitrary key/value labels (team, environment, region, cost tier, ...) to registry
+entries and filters entries with Kubernetes-style label selectors.
+
+Supported selector requirements, separated by commas (all must match):
+* key=value, key==value, key!=value
+* key in (v1,v2), key notin (v1,v2)
+* key (label present), !key (label absent)
+
+Equality and set-based requirements are resolved through an in-memory label index;
+the other requirements are checked against the labels of the candidate entries.
+*/
+
+/// Labels of a registry entry.
+pub type Labels = BTreeMap<String, String>;
+
+/// Operator of a selector requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectorOperator {
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+/// A single requirement of a label selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub key: String,
+    pub operator: SelectorOperator,
+    pub values: BTreeSet<String>,
+}
+
+impl Requirement {
+    pub fn matches(&self, labels: &Labels) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            SelectorOperator::Equals | SelectorOperator::In => value.is_some_and(|v| self.values.contains(v)),
+            SelectorOperator::NotEquals | SelectorOperator::NotIn => value.is_none_or(|v| !self.values.contains(v)),
+            SelectorOperator::Exists => value.is_some(),
+            SelectorOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+/// A label selector, e.g. "env=prod,team!=research".
+/// An empty selector matches every entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    pub requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements.iter().all(|requirement| requirement.matches(labels))
+    }
+}
+
+impl FromStr for LabelSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        let requirements = split_requirements(selector)
+            .into_iter()
+            .map(|requirement| parse_requirement(&requirement))
+            .collect::<anyhow::Result<Vec<Requirement>>>()?;
+        Ok(LabelSelector { requirements })
+    }
+}
+
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requirements = self
+            .requirements
+            .iter()
+            .map(|requirement| {
+                let values = requirement.values.iter().cloned().collect::<Vec<String>>().join(",");
+                match requirement.operator {
+                    SelectorOperator::Equals => format!("{}={}", requirement.key, values),
+                    SelectorOperator::NotEquals => format!("{}!={}", requirement.key, values),
+                    SelectorOperator::In => format!("{} in ({})", requirement.key, values),
+                    SelectorOperator::NotIn => format!("{} notin ({})", requirement.key, values),
+                    SelectorOperator::Exists => requirement.key.clone(),
+                    SelectorOperator::DoesNotExist => format!("!{}", requirement.key),
+                }
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+/// Splits a selector on the commas that are not inside a set of values.
+fn split_requirements(selector: &str) -> Vec<String> {
+    let mut requirements = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+
+    for c in selector.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                requirements.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    requirements.push(current);
+
+    requirements
+        .into_iter()
+        .map(|requirement| requirement.trim().to_string())
+        .filter(|requirement| !requirement.is_empty())
+        .collect()
+}
+
+fn parse_requirement(requirement: &str) -> anyhow::Result<Requirement> {
+    let single = |key: &str, operator: SelectorOperator, value: &str| -> anyhow::Result<Requirement> {
+        let key = validate_label_key(key.trim())?;
+        let value = validate_label_value(value.trim())?;
+        Ok(Requirement {
+            key,
+            operator,
+            values: BTreeSet::from([value]),
+        })
+    };
+
+    if let Some((key, value)) = requirement.split_once("!=") {
+        return single(key, SelectorOperator::NotEquals, value);
+    }
+    if let Some((key, value)) = requirement.split_once("==") {
+        return single(key, SelectorOperator::Equals, value);
+    }
+    if let Some((key, value)) = requirement.split_once('=') {
+        return single(key, SelectorOperator::Equals, value);
+    }
+
+    // Set-based requirements: "key in (a,b)" and "key notin (a,b)"
+    let tokens: Vec<&str> = requirement.splitn(2, char::is_whitespace).collect();
+    if tokens.len() == 2 {
+        let key = validate_label_key(tokens[0])?;
+        let rest = tokens[1].trim();
+        let (operator, values) = if let Some(values) = rest.strip_prefix("notin") {
+            (SelectorOperator::NotIn, values)
+        } else if let Some(values) = rest.strip_prefix("in") {
+            (SelectorOperator::In, values)
+        } else {
+            anyhow::bail!("Invalid selector requirement: '{}'", requirement);
+        };
+
+        let values = values
+            .trim()
+            .strip_prefix('(')
+            .and_then(|values| values.strip_suffix(')'))
+            .ok_or_else(|| anyhow::anyhow!("Expected a parenthesized list of values in '{}'", requirement))?;
+        let values = values
+            .split(',')
+            .map(|value| validate_label_value(value.trim()))
+            .collect::<anyhow::Result<BTreeSet<String>>>()?;
+        if values.is_empty() {
+            anyhow::bail!("Empty list of values in '{}'", requirement);
+        }
+        return Ok(Requirement { key, operator, values });
+    }
+
+    if let Some(key) = requirement.strip_prefix('!') {
+        return Ok(Requirement {
+            key: validate_label_key(key.trim())?,
+            operator: SelectorOperator::DoesNotExist,
+            values: BTreeSet::new(),
+        });
+    }
+
+    Ok(Requirement {
+        key: validate_label_key(requirement)?,
+        operator: SelectorOperator::Exists,
+        values: BTreeSet::new(),
+    })
+}
+
+/// Label keys are made of alphanumeric characters, '-', '_', '.' and '/'.
+pub fn validate_label_key(key: &str) -> anyhow::Result<String> {
+    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || "-_./".contains(c)) {
+        anyhow::bail!("Invalid label key: '{}'", key);
+    }
+    Ok(key.to_string())
+}
+
+/// Label values are made of alphanumeric characters, '-', '_' and '.', and may be empty.
+pub fn validate_label_value(value: &str) -> anyhow::Result<String> {
+    if !value.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c)) {
+        anyhow::bail!("Invalid label value: '{}'", value);
+    }
+    Ok(value.to_string())
+}
+
+/// Checks every key and value of a set of labels.
+pub fn validate_labels(labels: &Labels) -> anyhow::Result<()> {
+    for (key, value) in labels {
+        validate_label_key(key)?;
+        validate_label_value(value)?;
+    }
+    Ok(())
+}
+
+/// Query parameter used by the listing and search routes, e.g. /agents?selector=env=prod,team!=research
+#[derive(Debug, Default, Deserialize)]
+pub struct SelectorParams {
+    pub selector: Option<String>,
+}
+
+impl SelectorParams {
+    /// Parses the selector, an absent selector matching every entry.
+    pub fn parse(&self) -> Result<LabelSelector, (StatusCode, String)> {
+        parse_selector(self.selector.as_deref())
+    }
+}
+
+/// Parses an optional selector, mapping syntax errors to 400 Bad Request.
+pub fn parse_selector(selector: Option<&str>) -> Result<LabelSelector, (StatusCode, String)> {
+    match selector {
+        Some(selector) => selector.parse::<LabelSelector>().map_err(|e| {
+            info!("Invalid label selector '{}': {}", selector, e);
+            (StatusCode::BAD_REQUEST, e.to_string())
+        }),
+        None => Ok(LabelSelector::default()),
+    }
+}
+
+/// The labels of a registry entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledEntry {
+    #[serde(flatten)]
+    pub key: ResourceKey,
+    pub labels: Labels,
+}
+
+impl AppState {
+    /// Replaces the labels of an entry and updates the label index.
+    pub fn set_labels(&self, key: &ResourceKey, labels: Labels) {
+        self.unindex_labels(key);
+
+        if labels.is_empty() {
+            return;
+        }
+        for (label_key, label_value) in &labels {
+            self.label_index
+                .entry((label_key.clone(), label_value.clone()))
+                .or_default()
+                .insert(key.clone());
+        }
+        self.labels.insert(key.clone(), labels);
+    }
+
+    /// Removes the labels of an entry from the labels store and from the label index.
+    pub fn unindex_labels(&self, key: &ResourceKey) {
+        if let Some((_, previous)) = self.labels.remove(key) {
+            for label in previous {
+                if let Some(mut keys) = self.label_index.get_mut(&label) {
+                    keys.remove(key);
+                }
+                self.label_index.remove_if(&label, |_, keys| keys.is_empty());
+            }
+        }
+    }
+
+    /// Returns the labels of an entry (empty if it has none).
+    pub fn labels_of(&self, key: &ResourceKey) -> Labels {
+        self.labels.get(key).map(|e| e.value().clone()).unwrap_or_default()
+    }
+
+    /// Returns true if the entry matches the selector.
+    pub fn matches_selector(&self, key: &ResourceKey, selector: &LabelSelector) -> bool {
+        selector.is_empty() || selector.matches(&self.labels_of(key))
+    }
+
+    /// Returns the entries matching the selector, narrowed down through the label index when
+    /// the selector has equality or set-based requirements. Returns None if the selector
+    /// cannot be resolved through the index (every entry then has to be checked).
+    pub fn select_indexed(&self, selector: &LabelSelector) -> Option<HashSet<ResourceKey>> {
+        let mut selected: Option<HashSet<ResourceKey>> = None;
+
+        for requirement in &selector.requirements {
+            if !matches!(requirement.operator, SelectorOperator::Equals | SelectorOperator::In) {
+                continue;
+            }
+            let mut matching = HashSet::new();
+            for value in &requirement.values {
+                if let Some(keys) = self.label_index.get(&(requirement.key.clone(), value.clone())) {
+                    matching.extend(keys.iter().cloned());
+                }
+            }
+            selected = Some(match selected {
+                Some(previous) => previous.intersection(&matching).cloned().collect(),
+                None => matching,
+            });
+        }
+
+        selected.map(|keys| keys.into_iter().filter(|key| self.matches_selector(key, selector)).collect())
+    }
+
+    /// Returns the ids of the entries of the given kind that match the selector, sorted,
+    /// so that the lists (and their ETags) do not depend on the iteration order of the maps.
+    pub fn select_ids(&self, kind: ResourceKind, selector: &LabelSelector) -> Vec<String> {
+        let indexed = if selector.is_empty() { None } else { self.select_indexed(selector) };
+        let mut ids: Vec<String> = match indexed {
+            Some(selected) => selected
+                .into_iter()
+                .filter(|key| key.kind == kind && self.contains(key))
+                .map(|key| key.id)
+                .collect(),
+            None => {
+                let ids: Vec<String> = match kind {
+                    ResourceKind::Agent => self.db_agents.iter().map(|e| e.key().clone()).collect(),
+                    ResourceKind::Task => self.db_tasks.iter().map(|e| e.key().clone()).collect(),
+                    ResourceKind::Tool => self.db_tools.iter().map(|e| e.key().clone()).collect(),
+                };
+                ids.into_iter()
+                    .filter(|id| self.matches_selector(&ResourceKey::new(kind, id.as_str()), selector))
+                    .collect()
+            }
+        };
+        ids.sort();
+        ids
+    }
+
+    /// Returns the agents matching the selector.
+    pub fn select_agents(&self, selector: &LabelSelector) -> Vec<AgentDefinition> {
+        self.select_ids(ResourceKind::Agent, selector)
+            .into_iter()
+            .filter_map(|id| self.db_agents.get(&id).map(|e| e.value().clone()))
+            .collect()
+    }
+
+    /// Returns the tasks matching the selector.
+    pub fn select_tasks(&self, selector: &LabelSelector) -> Vec<TaskDefinition> {
+        self.select_ids(ResourceKind::Task, selector)
+            .into_iter()
+            .filter_map(|id| self.db_tasks.get(&id).map(|e| e.value().clone()))
+            .collect()
+    }
+
+    /// Returns the tools matching the selector.
+    pub fn select_tools(&self, selector: &LabelSelector) -> Vec<ToolDefinition> {
+        self.select_ids(ResourceKind::Tool, selector)
+            .into_iter()
+            .filter_map(|id| self.db_tools.get(&id).map(|e| e.value().clone()))
+            .collect()
+    }
+
+    /// Lists the labels of every labeled entry.
+    pub fn labeled_entries(&self) -> Vec<LabeledEntry> {
+        let mut entries: Vec<LabeledEntry> = self
+            .labels
+            .iter()
+            .map(|e| LabeledEntry {
+                key: e.key().clone(),
+                labels: e.value().clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+}
+
+/// Lists the labels of the entries matching the selector, e.g. /labels?selector=env=prod
+pub async fn list_labels(
+    State(state): State<AppState>,
+    Query(params): Query<SelectorParams>,
+) -> Result<Json<Vec<LabeledEntry>>, (StatusCode, String)> {
+    let selector = params.parse()?;
+    let entries = state
+        .labeled_entries()
+        .into_iter()
+        .filter(|entry| selector.matches(&entry.labels))
+        .collect();
+    Ok(Json(entries))
+}
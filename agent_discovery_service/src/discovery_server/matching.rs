@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use agent_models::registry::registry_models::TaskDefinition;
+
+use crate::discovery_server::registry::ResourceKind;
+use crate::discovery_server::server::AppState;
+use crate::embeddings::similarity_search::{generate_embedding, tokenize, Embedding, VectorDB};
+
+/*
+matching.rs
+resolves a registered task to the agents and tools able to perform it.
+
+Candidates are ranked by combining:
+* the lexical coverage of the task: share of the task words found in the candidate skills, name and description
+* the semantic similarity between the embeddings of the task and of the candidate
+Agents whose skills are mentioned by the task are found through the skills index.
+*/
+
+/// Weight of the lexical coverage in the final score, the remainder going to the semantic similarity.
+const LEXICAL_WEIGHT: f32 = 0.6;
+
+/// Default number of candidates returned.
+const DEFAULT_CANDIDATES_LIMIT: usize = 10;
+
+/// An agent or tool able to perform a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskCandidate {
+    pub kind: ResourceKind,
+    pub id: String,
+    pub name: String,
+    /// Final score, between 0 and 1.
+    pub score: f32,
+    /// Share of the task words covered by the candidate.
+    pub lexical_coverage: f32,
+    /// Cosine similarity between the task and candidate embeddings.
+    pub semantic_similarity: f32,
+    /// Skills of the candidate mentioned by the task (agents only).
+    pub matched_skills: Vec<String>,
+}
+
+/// Query parameters of the candidates endpoint, e.g. /tasks/{task_id}/candidates?limit=5
+#[derive(Debug, Deserialize)]
+pub struct CandidatesParams {
+    pub limit: Option<usize>,
+}
+
+impl AppState {
+    /// Ranks the registered agents and tools by how well they cover the task.
+    pub fn match_task_candidates(&self, task_def: &TaskDefinition, limit: usize) -> Vec<TaskCandidate> {
+        let task_text = format!("{} {}", task_def.name, task_def.description);
+        let task_tokens: HashSet<String> = tokenize(&task_text).into_iter().collect();
+        let task_embedding = generate_embedding(&task_text);
+
+        // Skills mentioned by the task, found through the skills index
+        let mut matched_skills: HashMap<String, Vec<String>> = HashMap::new();
+        for entry in self.skills_index.iter() {
+            let skill_tokens = tokenize(entry.key());
+            if !skill_tokens.is_empty() && skill_tokens.iter().all(|token| task_tokens.contains(token)) {
+                for agent_id in entry.value() {
+                    matched_skills.entry(agent_id.clone()).or_default().push(entry.key().clone());
+                }
+            }
+        }
+
+        let mut candidates = Vec::new();
+
+        for entry in self.db_agents.iter() {
+            let agent_def = entry.value();
+            let skill_names = agent_def
+                .skills
+                .iter()
+                .map(|skill| skill.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ");
+            let agent_text = format!("{} {} {}", agent_def.name, agent_def.description, skill_names);
+            let mut agent_skills = matched_skills.remove(&agent_def.id).unwrap_or_default();
+            agent_skills.sort();
+
+            candidates.push(score_candidate(
+                ResourceKind::Agent,
+                &agent_def.id,
+                &agent_def.name,
+                &agent_text,
+                &task_tokens,
+                &task_embedding,
+                agent_skills,
+            ));
+        }
+
+        for entry in self.db_tools.iter() {
+            let tool_def = entry.value();
+            let tool_text = format!("{} {}", tool_def.name, tool_def.description);
+
+            candidates.push(score_candidate(
+                ResourceKind::Tool,
+                &tool_def.id,
+                &tool_def.name,
+                &tool_text,
+                &task_tokens,
+                &task_embedding,
+                Vec::new(),
+            ));
+        }
+
+        candidates.retain(|candidate| candidate.score > 0.0);
+        candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.truncate(limit);
+        candidates
+    }
+}
+
+fn score_candidate(
+    kind: ResourceKind,
+    id: &str,
+    name: &str,
+    candidate_text: &str,
+    task_tokens: &HashSet<String>,
+    task_embedding: &Embedding,
+    matched_skills: Vec<String>,
+) -> TaskCandidate {
+    let candidate_tokens: HashSet<String> = tokenize(candidate_text).into_iter().collect();
+    let lexical_coverage = if task_tokens.is_empty() {
+        0.0
+    } else {
+        task_tokens.intersection(&candidate_tokens).count() as f32 / task_tokens.len() as f32
+    };
+    let semantic_similarity = VectorDB::cosine_similarity(task_embedding, &generate_embedding(candidate_text)).max(0.0);
+
+    TaskCandidate {
+        kind,
+        id: id.to_string(),
+        name: name.to_string(),
+        score: LEXICAL_WEIGHT * lexical_coverage + (1.0 - LEXICAL_WEIGHT) * semantic_similarity,
+        lexical_coverage,
+        semantic_similarity,
+        matched_skills,
+    }
+}
+
+/// Returns the agents and tools able to perform a registered task, best candidates first.
+pub async fn find_task_candidates(
+    State(state): State<AppState>,
+    Path(task_id): Path<String>,
+    Query(params): Query<CandidatesParams>,
+) -> Result<Json<Vec<TaskCandidate>>, StatusCode> {
+    info!("Received candidates request for task: {}", task_id);
+
+    let task_def = match state.db_tasks.get(&task_id) {
+        Some(entry) => entry.value().clone(),
+        None => {
+            info!("Candidates request failed: task '{}' is not registered", task_id);
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+
+    let candidates = state.match_task_candidates(&task_def, params.limit.unwrap_or(DEFAULT_CANDIDATES_LIMIT));
+    info!("Found {} candidates for task '{}'", candidates.len(), task_id);
+    Ok(Json(candidates))
+}
@@ -1 +1,16 @@
-pub mod server;
\ No newline at end of file
+pub mod server;
+pub mod registry;
+pub mod snapshot;
+pub mod static_config;
+pub mod audit;
+pub mod auth;
+pub mod matching;
+pub mod dependencies;
+pub mod labels;
+pub mod operations;
+pub mod grpc;
+pub mod search;
+pub mod duplicates;
+pub mod reputation;
+pub mod webhooks;
+pub mod etag;
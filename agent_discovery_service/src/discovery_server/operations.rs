@@ -0,0 +1,229 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::audit::RequestOrigin;
+use crate::discovery_server::auth::Principal;
+use crate::discovery_server::duplicates::NearDuplicate;
+use crate::discovery_server::labels::validate_labels;
+use crate::discovery_server::registry::{Registration, RegistryResponse, ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+operations.rs
+implements the registration and deregistration of registry entries on behalf of a caller.
+
+These operations are shared by every API exposed by the discovery server (HTTP, gRPC):
+they enforce the static configuration, ownership and referential integrity rules,
+record the change in the audit log and maintain labels and dependencies.
+*/
+
+/// A rejected operation: the status to report and the response explaining why.
+pub type Rejection = (StatusCode, RegistryResponse);
+
+/// A definition that can be stored in the registry.
+pub trait RegistryEntry: Clone + Serialize + Send + Sync + 'static {
+    const KIND: ResourceKind;
+
+    fn entry_id(&self) -> &str;
+    fn entry_name(&self) -> &str;
+
+    /// Stores the definition, returning the previous version.
+    fn upsert(state: &AppState, definition: Self) -> Option<Self>;
+    /// Removes the definition, returning it if it was registered.
+    fn remove(state: &AppState, id: &str) -> Option<Self>;
+    /// Returns the registered definition.
+    fn get(state: &AppState, id: &str) -> Option<Self>;
+    /// Returns the other registered entries doing the same thing as this definition.
+    fn near_duplicates(_state: &AppState, _definition: &Self) -> Vec<NearDuplicate> {
+        Vec::new()
+    }
+
+    fn key(&self) -> ResourceKey {
+        ResourceKey::new(Self::KIND, self.entry_id())
+    }
+}
+
+impl RegistryEntry for AgentDefinition {
+    const KIND: ResourceKind = ResourceKind::Agent;
+
+    fn entry_id(&self) -> &str {
+        &self.id
+    }
+
+    fn entry_name(&self) -> &str {
+        &self.name
+    }
+
+    fn upsert(state: &AppState, definition: Self) -> Option<Self> {
+        state.upsert_agent(definition)
+    }
+
+    fn remove(state: &AppState, id: &str) -> Option<Self> {
+        state.remove_agent(id)
+    }
+
+    fn get(state: &AppState, id: &str) -> Option<Self> {
+        state.db_agents.get(id).map(|e| e.value().clone())
+    }
+
+    fn near_duplicates(state: &AppState, definition: &Self) -> Vec<NearDuplicate> {
+        state.near_duplicate_agents(definition)
+    }
+}
+
+impl RegistryEntry for TaskDefinition {
+    const KIND: ResourceKind = ResourceKind::Task;
+
+    fn entry_id(&self) -> &str {
+        &self.id
+    }
+
+    fn entry_name(&self) -> &str {
+        &self.name
+    }
+
+    fn upsert(state: &AppState, definition: Self) -> Option<Self> {
+        state.upsert_task(definition)
+    }
+
+    fn remove(state: &AppState, id: &str) -> Option<Self> {
+        state.remove_task(id)
+    }
+
+    fn get(state: &AppState, id: &str) -> Option<Self> {
+        state.db_tasks.get(id).map(|e| e.value().clone())
+    }
+}
+
+impl RegistryEntry for ToolDefinition {
+    const KIND: ResourceKind = ResourceKind::Tool;
+
+    fn entry_id(&self) -> &str {
+        &self.id
+    }
+
+    fn entry_name(&self) -> &str {
+        &self.name
+    }
+
+    fn upsert(state: &AppState, definition: Self) -> Option<Self> {
+        state.upsert_tool(definition)
+    }
+
+    fn remove(state: &AppState, id: &str) -> Option<Self> {
+        state.remove_tool(id)
+    }
+
+    fn get(state: &AppState, id: &str) -> Option<Self> {
+        state.db_tools.get(id).map(|e| e.value().clone())
+    }
+
+    fn near_duplicates(state: &AppState, definition: &Self) -> Vec<NearDuplicate> {
+        state.near_duplicate_tools(definition)
+    }
+}
+
+/// Capitalized name of a kind, used in the responses ("Agent registered successfully").
+fn kind_label(kind: ResourceKind) -> &'static str {
+    match kind {
+        ResourceKind::Agent => "Agent",
+        ResourceKind::Task => "Task",
+        ResourceKind::Tool => "Tool",
+    }
+}
+
+impl AppState {
+    /// Registers (or updates) an entry on behalf of the principal.
+    /// Near-duplicates of the entry are reported as warnings, or rejected with 409 Conflict
+    /// in strict mode; strict defaults to the server configuration when None.
+    /// Updates of a registered entry are never rejected, their near-duplicates are warnings.
+    pub fn register<T: RegistryEntry>(
+        &self,
+        registration: Registration<T>,
+        strict: Option<bool>,
+        principal: &Principal,
+        origin: &RequestOrigin,
+    ) -> Result<RegistryResponse, Rejection> {
+        let kind = kind_label(T::KIND);
+        let definition = registration.definition;
+        let key = definition.key();
+
+        if let Some(Err(e)) = registration.labels.as_ref().map(validate_labels) {
+            return Err((StatusCode::BAD_REQUEST, RegistryResponse::new(e.to_string())));
+        }
+        if self.is_static(&key) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                RegistryResponse::new(format!("{} is statically configured and cannot be modified", kind)),
+            ));
+        }
+        let claimed = match self.claim_ownership(&key, principal) {
+            Ok(claimed) => claimed,
+            Err((status, message)) => return Err((status, RegistryResponse::new(message))),
+        };
+
+        let duplicate_warnings = T::near_duplicates(self, &definition)
+            .iter()
+            .map(|duplicate| duplicate.describe(&key))
+            .collect::<Vec<String>>();
+        // An entry registered before its look-alikes must stay able to update itself
+        let is_new = !self.contains(&key);
+        if is_new && !duplicate_warnings.is_empty() && strict.unwrap_or(self.duplicate_detection.strict) {
+            if claimed {
+                self.release_ownership(&key, principal);
+            }
+            return Err((
+                StatusCode::CONFLICT,
+                RegistryResponse::new(format!("{} has near-duplicates, it was not registered", kind)).with_warnings(duplicate_warnings),
+            ));
+        }
+
+        let previous = T::upsert(self, definition.clone());
+        self.audit_change(&key, origin, previous.as_ref(), Some(&definition));
+        // Registrations without labels or depends_on keep the ones declared before
+        if let Some(labels) = registration.labels {
+            self.set_labels(&key, labels);
+        }
+        let mut warnings = match &registration.depends_on {
+            Some(depends_on) => self.set_dependencies(&key, depends_on),
+            None => Vec::new(),
+        };
+        warnings.extend(duplicate_warnings);
+
+        Ok(RegistryResponse::new(format!("{} registered successfully", kind)).with_warnings(warnings))
+    }
+
+    /// Deregisters an entry on behalf of the principal.
+    /// Rejected with 409 Conflict if other entries depend on it, unless force is set.
+    pub fn deregister<T: RegistryEntry>(
+        &self,
+        id: &str,
+        force: bool,
+        principal: &Principal,
+        origin: &RequestOrigin,
+    ) -> Result<RegistryResponse, Rejection> {
+        let kind = kind_label(T::KIND);
+        let key = ResourceKey::new(T::KIND, id);
+
+        if self.is_static(&key) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                RegistryResponse::new(format!("{} is statically configured and cannot be deregistered", kind)),
+            ));
+        }
+        if let Err((status, message)) = self.check_ownership(&key, principal) {
+            return Err((status, RegistryResponse::new(message)));
+        }
+        // The dependents are checked and the entry removed as one dependency graph update
+        let (warnings, removed) = {
+            let _graph = self.dependency_lock.lock().unwrap();
+            let warnings = self.check_deregistration(&key, force)?;
+            (warnings, T::remove(self, id))
+        };
+        self.audit_change(&key, origin, removed.as_ref(), None);
+
+        Ok(RegistryResponse::new(format!("{} deregistered successfully", kind)).with_warnings(warnings))
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::audit::RequestOrigin;
+use crate::discovery_server::labels::Labels;
+use crate::discovery_server::server::AppState;
+
+/*
+registry.rs
+holds the primitive operations on the in-memory registry held in AppState.
+
+Every path that mutates the registry (HTTP handlers, snapshot import, ...) goes
+through these helpers so that the secondary indexes stay consistent with the
+stored definitions.
+*/
+
+/// The kinds of resources held by the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    Agent,
+    Task,
+    Tool,
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceKind::Agent => write!(f, "agent"),
+            ResourceKind::Task => write!(f, "task"),
+            ResourceKind::Tool => write!(f, "tool"),
+        }
+    }
+}
+
+impl FromStr for ResourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "agent" => Ok(ResourceKind::Agent),
+            "task" => Ok(ResourceKind::Task),
+            "tool" => Ok(ResourceKind::Tool),
+            other => Err(anyhow::anyhow!("Unknown resource kind: {}", other)),
+        }
+    }
+}
+
+/// Identifies a registry entry. Ids are only unique within a kind.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ResourceKey {
+    pub kind: ResourceKind,
+    pub id: String,
+}
+
+impl ResourceKey {
+    pub fn new(kind: ResourceKind, id: impl Into<String>) -> Self {
+        ResourceKey { kind, id: id.into() }
+    }
+
+    pub fn agent(id: impl Into<String>) -> Self {
+        Self::new(ResourceKind::Agent, id)
+    }
+
+    pub fn task(id: impl Into<String>) -> Self {
+        Self::new(ResourceKind::Task, id)
+    }
+
+    pub fn tool(id: impl Into<String>) -> Self {
+        Self::new(ResourceKind::Tool, id)
+    }
+}
+
+impl fmt::Display for ResourceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.id)
+    }
+}
+
+/// Registration payload: a definition along with the registry metadata describing it.
+/// The definition fields are flattened, so a bare definition is a valid registration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration<T> {
+    #[serde(flatten)]
+    pub definition: T,
+    /// Registry entries this entry depends on, as "kind:id" or as a bare id.
+    /// None leaves the declared dependencies unchanged (e.g. a heartbeat), an empty list removes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    /// Arbitrary key/value labels (team, environment, region, ...).
+    /// None leaves the labels unchanged (e.g. a heartbeat), an empty map removes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Labels>,
+}
+
+impl<T> From<T> for Registration<T> {
+    fn from(definition: T) -> Self {
+        Registration {
+            definition,
+            depends_on: None,
+            labels: None,
+        }
+    }
+}
+
+/// Response of the registration and deregistration routes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryResponse {
+    pub message: String,
+    /// Non blocking issues detected while processing the request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl RegistryResponse {
+    pub fn new(message: impl Into<String>) -> Self {
+        RegistryResponse {
+            message: message.into(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+}
+
+impl AppState {
+    /// Returns true if the entry was loaded from the static configuration directory.
+    /// Static entries cannot be modified or deregistered over HTTP.
+    pub fn is_static(&self, key: &ResourceKey) -> bool {
+        self.static_entries.contains_key(key)
+    }
+
+    /// Stores an AgentDefinition and indexes its skills.
+    /// Returns the definition previously registered under the same id, if any.
+    pub fn upsert_agent(&self, agent_def: AgentDefinition) -> Option<AgentDefinition> {
+        let agent_id = agent_def.id.clone();
+
+        // Drop the skills of the previous version, they may have changed
+        let previous = self.db_agents.get(&agent_id).map(|e| e.value().clone());
+        if let Some(previous_def) = &previous {
+            self.unindex_agent_skills(previous_def);
+        }
+
+        // Index the agent's skills
+        for skill in &agent_def.skills {
+            self.skills_index
+                .entry(skill.name.to_lowercase())
+                .or_default()
+                .insert(agent_id.clone());
+        }
+
+        self.index_agent(&agent_def);
+        self.db_agents.insert(agent_id, agent_def);
+        previous
+    }
+
+    /// Removes an AgentDefinition and its entries in the skills index.
+    /// Returns the removed definition, if it was registered.
+    pub fn remove_agent(&self, agent_id: &str) -> Option<AgentDefinition> {
+        let (_, removed) = self.db_agents.remove(agent_id)?;
+        self.unindex_agent_skills(&removed);
+        self.unindex_for_search(&ResourceKey::agent(agent_id));
+        self.owners.remove(&ResourceKey::agent(agent_id));
+        self.dependencies.remove(&ResourceKey::agent(agent_id));
+        self.unindex_labels(&ResourceKey::agent(agent_id));
+        Some(removed)
+    }
+
+    /// Removes the agent from the skills index, cleaning up skills left without agents.
+    fn unindex_agent_skills(&self, agent_def: &AgentDefinition) {
+        for skill in &agent_def.skills {
+            let skill_key = skill.name.to_lowercase();
+            if let Some(mut agents_with_skill) = self.skills_index.get_mut(&skill_key) {
+                agents_with_skill.remove(&agent_def.id);
+            }
+            self.skills_index.remove_if(&skill_key, |_, agents| agents.is_empty());
+        }
+    }
+
+    /// Stores a TaskDefinition. Returns the previous definition, if any.
+    pub fn upsert_task(&self, task_def: TaskDefinition) -> Option<TaskDefinition> {
+        self.index_task(&task_def);
+        self.db_tasks.insert(task_def.id.clone(), task_def)
+    }
+
+    /// Removes a TaskDefinition. Returns the removed definition, if any.
+    pub fn remove_task(&self, task_id: &str) -> Option<TaskDefinition> {
+        self.owners.remove(&ResourceKey::task(task_id));
+        self.dependencies.remove(&ResourceKey::task(task_id));
+        self.unindex_labels(&ResourceKey::task(task_id));
+        self.unindex_for_search(&ResourceKey::task(task_id));
+        self.db_tasks.remove(task_id).map(|(_, task_def)| task_def)
+    }
+
+    /// Stores a ToolDefinition. Returns the previous definition, if any.
+    pub fn upsert_tool(&self, tool_def: ToolDefinition) -> Option<ToolDefinition> {
+        self.index_tool(&tool_def);
+        self.db_tools.insert(tool_def.id.clone(), tool_def)
+    }
+
+    /// Removes a ToolDefinition. Returns the removed definition, if any.
+    pub fn remove_tool(&self, tool_id: &str) -> Option<ToolDefinition> {
+        self.owners.remove(&ResourceKey::tool(tool_id));
+        self.dependencies.remove(&ResourceKey::tool(tool_id));
+        self.unindex_labels(&ResourceKey::tool(tool_id));
+        self.unindex_for_search(&ResourceKey::tool(tool_id));
+        self.db_tools.remove(tool_id).map(|(_, tool_def)| tool_def)
+    }
+
+    /// Removes every agent, task and tool, along with the indexes.
+    /// Static entries are kept, they are owned by the static configuration directory.
+    pub fn clear_registry(&self, origin: &RequestOrigin) {
+        let agent_ids: Vec<String> = self.db_agents.iter().map(|e| e.key().clone()).collect();
+        for agent_id in agent_ids {
+            let key = ResourceKey::agent(agent_id.as_str());
+            if !self.is_static(&key) {
+                let removed = self.remove_agent(&agent_id);
+                self.audit_change(&key, origin, removed.as_ref(), None);
+            }
+        }
+
+        let task_ids: Vec<String> = self.db_tasks.iter().map(|e| e.key().clone()).collect();
+        for task_id in task_ids {
+            let key = ResourceKey::task(task_id.as_str());
+            if !self.is_static(&key) {
+                let removed = self.remove_task(&task_id);
+                self.audit_change(&key, origin, removed.as_ref(), None);
+            }
+        }
+
+        let tool_ids: Vec<String> = self.db_tools.iter().map(|e| e.key().clone()).collect();
+        for tool_id in tool_ids {
+            let key = ResourceKey::tool(tool_id.as_str());
+            if !self.is_static(&key) {
+                let removed = self.remove_tool(&tool_id);
+                self.audit_change(&key, origin, removed.as_ref(), None);
+            }
+        }
+    }
+}
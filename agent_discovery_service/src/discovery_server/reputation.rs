@@ -0,0 +1,184 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::discovery_server::registry::ResourceKind;
+use crate::discovery_server::server::AppState;
+
+/*
+reputation.rs
+keeps the reputation of the agents, derived from the scores given by the evaluation service judge.
+
+Scores are either pulled periodically from the /scores endpoint of the evaluation service,
+or pushed by an admin to POST /admin/reputation. The reputations are listed by GET /reputation,
+and used to rank agents when searching with sort=reputation.
+
+The reputation of an agent is its average judge score mapped to [0, 1], smoothed towards
+a neutral prior so that a couple of evaluations do not outweigh a long track record.
+Agents never evaluated have the neutral reputation.
+*/
+
+/// Reputation of agents without evaluations.
+pub const NEUTRAL_REPUTATION: f32 = 0.5;
+
+/// Weight of the neutral prior, in number of evaluations.
+const PRIOR_EVALUATIONS: f32 = 3.0;
+
+/// Bounds of the judge scores (see the judge prompt of the evaluation service).
+const MIN_JUDGE_SCORE: f32 = 1.0;
+const MAX_JUDGE_SCORE: f32 = 10.0;
+
+/// Aggregate judge score of an agent, as reported by the evaluation service /scores endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentScoreReport {
+    pub agent_id: String,
+    pub evaluations: usize,
+    /// Average judge score, between 1 and 10.
+    pub average_score: f64,
+}
+
+/// Reputation of an agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentReputation {
+    pub agent_id: String,
+    /// Between 0 and 1.
+    pub reputation: f32,
+    pub evaluations: usize,
+    pub average_score: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<AgentScoreReport> for AgentReputation {
+    fn from(report: AgentScoreReport) -> Self {
+        let normalized = ((report.average_score as f32 - MIN_JUDGE_SCORE) / (MAX_JUDGE_SCORE - MIN_JUDGE_SCORE)).clamp(0.0, 1.0);
+        let evaluations = report.evaluations as f32;
+        let reputation = (evaluations * normalized + PRIOR_EVALUATIONS * NEUTRAL_REPUTATION) / (evaluations + PRIOR_EVALUATIONS);
+
+        AgentReputation {
+            agent_id: report.agent_id,
+            reputation,
+            evaluations: report.evaluations,
+            average_score: report.average_score,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Orders of the search results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Best matches first.
+    #[default]
+    Relevance,
+    /// Best matches weighted by the reputation of the agents first.
+    Reputation,
+}
+
+impl FromStr for SortOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "relevance" => Ok(SortOrder::Relevance),
+            "reputation" => Ok(SortOrder::Reputation),
+            other => Err(anyhow::anyhow!("Unknown sort order: {}", other)),
+        }
+    }
+}
+
+impl AppState {
+    /// Records the scores reported by the evaluation service.
+    pub fn update_reputations(&self, reports: Vec<AgentScoreReport>) -> usize {
+        let count = reports.len();
+        for report in reports {
+            let reputation = AgentReputation::from(report);
+            self.reputations.insert(reputation.agent_id.clone(), reputation);
+        }
+        count
+    }
+
+    /// Reputation of an entry, neutral for agents without evaluations and for tasks and tools.
+    pub fn reputation_of(&self, kind: ResourceKind, id: &str) -> f32 {
+        match kind {
+            ResourceKind::Agent => self
+                .reputations
+                .get(id)
+                .map(|e| e.value().reputation)
+                .unwrap_or(NEUTRAL_REPUTATION),
+            _ => NEUTRAL_REPUTATION,
+        }
+    }
+
+    /// Weight applied to the relevance of an entry when sorting by reputation:
+    /// between 0.5 and 1.5, neutral entries keeping their relevance.
+    pub fn reputation_weight(&self, kind: ResourceKind, id: &str) -> f32 {
+        0.5 + self.reputation_of(kind, id)
+    }
+}
+
+/// Pulls the agent scores from the evaluation service at a fixed interval.
+pub struct ReputationSync {
+    evaluation_service_url: String,
+    poll_interval: Duration,
+    client: reqwest::Client,
+}
+
+impl ReputationSync {
+    pub fn new(evaluation_service_url: impl Into<String>, poll_interval: Duration) -> Self {
+        ReputationSync {
+            evaluation_service_url: evaluation_service_url.into(),
+            poll_interval,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Fetches the scores once and updates the reputations.
+    pub async fn sync(&self, state: &AppState) -> anyhow::Result<usize> {
+        let url = format!("{}/scores", self.evaluation_service_url);
+        let reports = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<AgentScoreReport>>()
+            .await?;
+        Ok(state.update_reputations(reports))
+    }
+
+    /// Polls the evaluation service in the background.
+    pub fn spawn(self, state: AppState) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                match self.sync(&state).await {
+                    Ok(count) => info!("Updated the reputation of {} agents", count),
+                    Err(e) => warn!("Failed to pull agent scores from {}: {}", self.evaluation_service_url, e),
+                }
+            }
+        })
+    }
+}
+
+/// Lists the reputation of the evaluated agents, best first.
+pub async fn list_reputations(State(state): State<AppState>) -> Json<Vec<AgentReputation>> {
+    let mut reputations: Vec<AgentReputation> = state.reputations.iter().map(|e| e.value().clone()).collect();
+    reputations.sort_by(|a, b| b.reputation.partial_cmp(&a.reputation).unwrap_or(std::cmp::Ordering::Equal));
+    Json(reputations)
+}
+
+/// Records agent scores pushed by the evaluation service (or an operator).
+pub async fn push_reputations(
+    State(state): State<AppState>,
+    Json(reports): Json<Vec<AgentScoreReport>>,
+) -> (StatusCode, String) {
+    info!("Received reputation update for {} agents", reports.len());
+    let count = state.update_reputations(reports);
+    (StatusCode::OK, format!("Updated the reputation of {} agents", count))
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::labels::parse_selector;
+use crate::discovery_server::registry::{ResourceKey, ResourceKind};
+use crate::discovery_server::reputation::SortOrder;
+use crate::discovery_server::server::AppState;
+use crate::embeddings::lexical_search::Bm25Index;
+use crate::embeddings::similarity_search::{generate_embedding, SearchableAgent, VectorDB};
+
+/*
+search.rs
+implements the free text search across agents, tasks and tools.
+
+Two rankings are computed for a query:
+* a lexical ranking, BM25 over the ids, names, descriptions and skills of the entries,
+  which catches exact names and keywords
+* a semantic ranking, the similarity between the embeddings of the query and of the entries,
+  which catches paraphrases
+and merged with reciprocal rank fusion: an entry scores 1/(k + rank) for each ranking it appears in.
+*/
+
+/// Rank offset of the reciprocal rank fusion, dampening the weight of the top ranks.
+const RRF_K: f32 = 60.0;
+
+/// Default number of hits returned.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+
+/// Weight of the ids, names and skills relative to the descriptions in the lexical index.
+const KEYWORD_FIELD_WEIGHT: u32 = 2;
+
+/// Lexical and vector indexes of the registry entries, kept in sync with the registry.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    lexical: Bm25Index,
+    vectors: VectorDB,
+    /// Key: document id ("kind:id"), Value: the entry it describes and its name.
+    entries: HashMap<String, (ResourceKey, String)>,
+}
+
+impl SearchIndex {
+    /// Indexes an entry, replacing its previous version.
+    pub fn upsert(&mut self, key: &ResourceKey, name: &str, description: &str, skills: &[String]) {
+        let document_id = key.to_string();
+        let skills = skills.join(" ");
+
+        self.lexical.upsert(
+            &document_id,
+            &[
+                (key.id.as_str(), KEYWORD_FIELD_WEIGHT),
+                (name, KEYWORD_FIELD_WEIGHT),
+                (skills.as_str(), KEYWORD_FIELD_WEIGHT),
+                (description, 1),
+            ],
+        );
+        self.vectors.upsert_agent(SearchableAgent {
+            id: document_id.clone(),
+            name: name.to_string(),
+            description: description.to_string(),
+            embedding: generate_embedding(&format!("{} {} {}", name, description, skills)),
+        });
+        self.entries.insert(document_id, (key.clone(), name.to_string()));
+    }
+
+    pub fn remove(&mut self, key: &ResourceKey) {
+        let document_id = key.to_string();
+        self.lexical.remove(&document_id);
+        self.vectors.remove_agent(&document_id);
+        self.entries.remove(&document_id);
+    }
+
+    /// Ranks every entry matching the query, best first.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let pool = self.entries.len();
+        let mut hits: HashMap<&str, SearchHit> = HashMap::new();
+
+        for (rank, (document_id, score)) in self.lexical.search(query, pool).into_iter().enumerate() {
+            if let Some(hit) = self.hit(&mut hits, &document_id) {
+                hit.lexical_score = Some(score);
+                hit.score += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+
+        let query_embedding = generate_embedding(query);
+        let similar = self.vectors.find_similar_scored(&query_embedding, pool);
+        let relevant = similar.into_iter().filter(|(similarity, _)| *similarity > 0.0);
+        for (rank, (similarity, searchable)) in relevant.enumerate() {
+            if let Some(hit) = self.hit(&mut hits, &searchable.id) {
+                hit.semantic_similarity = Some(similarity);
+                hit.score += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+
+        let mut ranked: Vec<SearchHit> = hits.into_values().collect();
+        ranked.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (a.kind, &a.id).cmp(&(b.kind, &b.id)))
+        });
+        ranked
+    }
+
+    /// Returns the hit of a document, creating it on its first match.
+    fn hit<'a, 'h>(&'a self, hits: &'h mut HashMap<&'a str, SearchHit>, document_id: &str) -> Option<&'h mut SearchHit> {
+        let (document_id, (key, name)) = self.entries.get_key_value(document_id)?;
+        Some(hits.entry(document_id.as_str()).or_insert_with(|| SearchHit {
+            kind: key.kind,
+            id: key.id.clone(),
+            name: name.clone(),
+            score: 0.0,
+            lexical_score: None,
+            semantic_similarity: None,
+            reputation: None,
+        }))
+    }
+}
+
+/// An entry matching a search query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub kind: ResourceKind,
+    pub id: String,
+    pub name: String,
+    /// Reciprocal rank fusion score of the lexical and semantic rankings.
+    pub score: f32,
+    /// BM25 score, if the entry contains words of the query.
+    pub lexical_score: Option<f32>,
+    /// Cosine similarity between the query and entry embeddings, if positive.
+    pub semantic_similarity: Option<f32>,
+    /// Reputation of the entry, when sorting by reputation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reputation: Option<f32>,
+}
+
+/// Query parameters of the search endpoint, e.g. /search?q=convert currency&kind=tool&limit=5
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub q: Option<String>,
+    pub kind: Option<ResourceKind>,
+    pub selector: Option<String>,
+    pub limit: Option<usize>,
+    /// sort=reputation weights the relevance of the agents by their reputation.
+    #[serde(default)]
+    pub sort: SortOrder,
+}
+
+impl AppState {
+    pub fn index_agent(&self, agent_def: &AgentDefinition) {
+        let skills = agent_def.skills.iter().map(|skill| skill.name.clone()).collect::<Vec<String>>();
+        self.search_index
+            .write()
+            .unwrap()
+            .upsert(&ResourceKey::agent(agent_def.id.as_str()), &agent_def.name, &agent_def.description, &skills);
+    }
+
+    pub fn index_task(&self, task_def: &TaskDefinition) {
+        self.search_index
+            .write()
+            .unwrap()
+            .upsert(&ResourceKey::task(task_def.id.as_str()), &task_def.name, &task_def.description, &[]);
+    }
+
+    pub fn index_tool(&self, tool_def: &ToolDefinition) {
+        self.search_index
+            .write()
+            .unwrap()
+            .upsert(&ResourceKey::tool(tool_def.id.as_str()), &tool_def.name, &tool_def.description, &[]);
+    }
+
+    pub fn unindex_for_search(&self, key: &ResourceKey) {
+        self.search_index.write().unwrap().remove(key);
+    }
+
+    /// Weights the score of the hits by their reputation and sorts them again.
+    pub fn weight_by_reputation(&self, hits: &mut [SearchHit]) {
+        for hit in hits.iter_mut() {
+            hit.reputation = Some(self.reputation_of(hit.kind, &hit.id));
+            hit.score *= self.reputation_weight(hit.kind, &hit.id);
+        }
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    /// Searches the registry, returning the best hits of the given kind (all kinds if None).
+    pub fn search(&self, query: &str, kind: Option<ResourceKind>, limit: usize) -> Vec<SearchHit> {
+        let mut hits = self.search_index.read().unwrap().search(query);
+        hits.retain(|hit| kind.is_none_or(|kind| hit.kind == kind));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Searches agents, tasks and tools matching a free text query, best hits first.
+pub async fn search_registry(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchHit>>, (StatusCode, String)> {
+    let query = match params.q.as_deref().map(str::trim) {
+        Some(q) if !q.is_empty() => q.to_string(),
+        _ => {
+            info!("Search request failed: 'q' query parameter is missing");
+            return Err((StatusCode::BAD_REQUEST, "Missing query parameter 'q'".to_string()));
+        }
+    };
+    let selector = parse_selector(params.selector.as_deref())?;
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+
+    info!("Received search request for query: {}", query);
+
+    let mut hits = state.search(&query, params.kind, usize::MAX);
+    hits.retain(|hit| state.matches_selector(&ResourceKey::new(hit.kind, hit.id.as_str()), &selector));
+    if params.sort == SortOrder::Reputation {
+        state.weight_by_reputation(&mut hits);
+    }
+    hits.truncate(limit);
+
+    info!("Found {} hits for query '{}'", hits.len(), query);
+    Ok(Json(hits))
+}
@@ -1,18 +1,39 @@
 use dashmap::DashMap;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json, Router,
-    routing::{get, post},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    Extension, Json, Router,
+    routing::{delete, get, post},
 };
+use serde::Deserialize;
 use tracing::info;
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 
 //use crate::model::models::{AgentDefinition, TaskDefinition, ToolDefinition};
 use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
 
+use crate::discovery_server::audit::{list_audit_records, AuditLog, RequestOrigin};
+use crate::discovery_server::auth::{require_admin, require_auth, AuthConfig, Principal};
+use crate::discovery_server::matching::find_task_candidates;
+use crate::discovery_server::duplicates::DuplicateDetectionConfig;
+use crate::discovery_server::etag::conditional_json;
+use crate::discovery_server::grpc::serve_grpc;
+use crate::discovery_server::dependencies::{get_dependency_graph, DeregisterParams};
+use crate::discovery_server::labels::{list_labels, parse_selector, LabelSelector, Labels, SelectorParams};
+use crate::discovery_server::operations::Rejection;
+use crate::discovery_server::registry::{Registration, RegistryResponse, ResourceKey, ResourceKind};
+use crate::discovery_server::reputation::{list_reputations, push_reputations, AgentReputation, SortOrder};
+use crate::discovery_server::search::{search_registry, SearchIndex};
+use crate::discovery_server::webhooks::{
+    clear_dead_letters, create_webhook, delete_webhook, list_dead_letters, list_webhooks, WebhookConfig, WebhookRegistry,
+};
+use crate::discovery_server::snapshot::{export_snapshot, import_snapshot};
+
 // This is a sample and simple implementation
 
 // todo: we should add a notion of context, that would be used to segment resources for different context
@@ -28,21 +49,66 @@ pub struct AppState {
     pub db_tasks: Arc<DashMap<String, TaskDefinition>>,
     /// In-memory database for registered tools. Key: tool_id, Value: ToolDefinition.
     pub db_tools: Arc<DashMap<String, ToolDefinition>>,
+    /// Entries loaded from the static configuration directory. Key: entry, Value: source file.
+    pub static_entries: Arc<DashMap<ResourceKey, PathBuf>>,
+    /// Append-only history of the changes made to the registry.
+    pub audit: Arc<AuditLog>,
+    /// Authentication settings for the mutating routes.
+    pub auth: Arc<AuthConfig>,
+    /// Principal owning each entry. Key: entry, Value: principal id.
+    pub owners: Arc<DashMap<ResourceKey, String>>,
+    /// Dependencies declared by each entry. Key: entry, Value: the entries it depends on.
+    pub dependencies: Arc<DashMap<ResourceKey, BTreeSet<ResourceKey>>>,
+    /// Serializes the dependency updates with the deregistrations checking the dependents.
+    pub dependency_lock: Arc<Mutex<()>>,
+    /// Labels of each entry. Key: entry, Value: labels.
+    pub labels: Arc<DashMap<ResourceKey, Labels>>,
+    /// In-memory index for labels. Key: (label key, label value), Value: Set of entries.
+    pub label_index: Arc<DashMap<(String, String), HashSet<ResourceKey>>>,
+    /// Lexical and vector indexes used by the free text search.
+    pub search_index: Arc<RwLock<SearchIndex>>,
+    /// Settings of the near-duplicate detection at registration time.
+    pub duplicate_detection: Arc<DuplicateDetectionConfig>,
+    /// Reputation of the evaluated agents. Key: agent_id, Value: reputation.
+    pub reputations: Arc<DashMap<String, AgentReputation>>,
+    /// Webhook subscriptions notified of the registry changes.
+    pub webhooks: Arc<WebhookRegistry>,
+}
+
+/// Settings of the discovery server.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryServerConfig {
+    pub auth: AuthConfig,
+    pub duplicate_detection: DuplicateDetectionConfig,
+    pub webhooks: WebhookConfig,
+}
+
+/// Query parameters of the registration routes, e.g. /agents/register?strict=true
+#[derive(Debug, Default, Deserialize)]
+pub struct RegisterParams {
+    /// Reject near-duplicates instead of warning about them. Defaults to the server configuration.
+    pub strict: Option<bool>,
 }
 
 /// The discovery server, responsible for agent, task, and tool registration and search.
 pub struct DiscoveryServer {
     pub uri: String,
     pub app: Router,
+    pub state: AppState,
 }
 
 impl DiscoveryServer {
     pub async fn new(uri: String) -> anyhow::Result<Self> {
+        Self::with_config(uri, DiscoveryServerConfig::default()).await
+    }
+
+    pub async fn with_config(uri: String, config: DiscoveryServerConfig) -> anyhow::Result<Self> {
         // Initialize the in-memory stores
         let db_agents = DashMap::new();
         let skills_index = DashMap::new();
         let db_tasks = DashMap::new();
         let db_tools = DashMap::new();
+        let static_entries = DashMap::new();
 
         // Create the application state
         let app_state = AppState {
@@ -50,36 +116,104 @@ impl DiscoveryServer {
             skills_index: Arc::new(skills_index),
             db_tasks: Arc::new(db_tasks),
             db_tools: Arc::new(db_tools),
+            static_entries: Arc::new(static_entries),
+            audit: Arc::new(AuditLog::new()),
+            auth: Arc::new(config.auth),
+            owners: Arc::new(DashMap::new()),
+            dependencies: Arc::new(DashMap::new()),
+            dependency_lock: Arc::new(Mutex::new(())),
+            labels: Arc::new(DashMap::new()),
+            label_index: Arc::new(DashMap::new()),
+            search_index: Arc::new(RwLock::new(SearchIndex::default())),
+            duplicate_detection: Arc::new(config.duplicate_detection),
+            reputations: Arc::new(DashMap::new()),
+            webhooks: Arc::new(WebhookRegistry::new(config.webhooks)),
         };
 
+        // Deliver the registry changes to the webhook subscriptions
+        app_state.webhooks.clone().spawn_dispatcher(app_state.audit.subscribe());
+
+        // Routes modifying the registry require an authenticated caller
+        let mutating_routes = Router::new()
+            .route("/agents/register", post(register_agent_definition))
+            .route("/agents/deregister", post(deregister_agent_definition))
+            .route("/tasks/register", post(register_task_definition))
+            .route("/tasks/deregister", post(deregister_task_definition))
+            .route("/tools/register", post(register_tool_definition))
+            .route("/tools/deregister", post(deregister_tool_definition))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), require_auth));
+
+        // Administration routes are restricted to the admin role
+        let admin_routes = Router::new()
+            .route("/admin/snapshot", get(export_snapshot).post(import_snapshot))
+            .route("/admin/audit", get(list_audit_records))
+            .route("/admin/reputation", post(push_reputations))
+            .route("/admin/webhooks", get(list_webhooks).post(create_webhook))
+            .route("/admin/webhooks/{subscription_id}", delete(delete_webhook))
+            .route("/admin/webhooks/dead-letters", get(list_dead_letters).delete(clear_dead_letters))
+            .route_layer(middleware::from_fn(require_admin))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), require_auth));
+
         // Configure the API routes
         let app = Router::new()
             .route("/", get(root))
             // Agent Definition Routes
-            .route("/agents/register", post(register_agent_definition))
-            .route("/agents/deregister", post(deregister_agent_definition))
             .route("/agents", get(list_agent_definitions))
             .route("/agents/search", get(search_agents_by_skill))
+            .route("/agents/{agent_id}", get(get_agent_definition))
             // Task Definition Routes
-            .route("/tasks/register", post(register_task_definition))
             .route("/tasks", get(list_task_definitions))
+            .route("/tasks/{task_id}/candidates", get(find_task_candidates))
             // Tool Definition Routes
-            .route("/tools/register", post(register_tool_definition))
             .route("/tools", get(list_tool_definitions))
             // All resources
             .route("/resources", get(list_available_resources))
-            .with_state(app_state);
-
-        Ok(Self { uri, app })
+            .route("/search", get(search_registry))
+            .route("/reputation", get(list_reputations))
+            .route("/graph", get(get_dependency_graph))
+            .route("/labels", get(list_labels))
+            .merge(mutating_routes)
+            .merge(admin_routes)
+            .with_state(app_state.clone());
+
+        Ok(Self { uri, app, state: app_state })
     }
 
     /// Start the HTTP server.
     pub async fn start_http(&self) -> anyhow::Result<()> {
         let listener = tokio::net::TcpListener::bind(&self.uri).await?;
         println!("Discovery Server started on {}", self.uri);
-        axum::serve(listener, self.app.clone()).await?;
+        // Connection info is used to record the source address of registry changes
+        axum::serve(listener, self.app.clone().into_make_service_with_connect_info::<SocketAddr>()).await?;
         Ok(())
     }
+
+    /// Start the gRPC server, sharing the state of the HTTP server.
+    pub async fn start_grpc(&self, grpc_uri: &str) -> anyhow::Result<()> {
+        let addr: SocketAddr = grpc_uri.parse()?;
+        println!("Discovery gRPC Server started on {}", addr);
+        serve_grpc(self.state.clone(), addr).await
+    }
+}
+
+impl AppState {
+    /// Returns the agents possessing a skill and matching the label selector.
+    pub fn agents_with_skill(&self, skill: &str, selector: &LabelSelector) -> Vec<AgentDefinition> {
+        let mut found_agents = Vec::new();
+        // Look up the skill in the index
+        if let Some(agent_ids) = self.skills_index.get(&skill.to_lowercase()) {
+            // Retrieve the full AgentDefinition for each matching agent ID
+            for id in agent_ids.iter() {
+                if !self.matches_selector(&ResourceKey::agent(id.as_str()), selector) {
+                    continue;
+                }
+                if let Some(agent_def) = self.db_agents.get(id) {
+                    found_agents.push(agent_def.value().clone());
+                }
+            }
+        }
+        found_agents
+    }
 }
 
 /// Root endpoint for basic health checks.
@@ -87,71 +221,80 @@ async fn root() -> &'static str {
     "Hello, Swarm Discovery Service!"
 }
 
+/// Maps the outcome of a registry operation to an HTTP response.
+fn into_registry_response(success: StatusCode, outcome: Result<RegistryResponse, Rejection>) -> (StatusCode, Json<RegistryResponse>) {
+    match outcome {
+        Ok(response) => (success, Json(response)),
+        Err((status, response)) => (status, Json(response)),
+    }
+}
+
 // Align agent registration from AgentServer and registration
 
 /// Registers an AgentDefinition and indexes its skills.
+/// Near-duplicates of the agent are reported as warnings, or rejected with strict=true.
 async fn register_agent_definition(
     State(state): State<AppState>,
-    Json(agent_def): Json<AgentDefinition>,
-) -> impl IntoResponse {
-    info!("Received register request for agent: {}", agent_def.name);
-    let agent_id = agent_def.id.clone();
-    let agent_skills = agent_def.skills.clone();
-
-    // Index the agent's skills
-    for skill in agent_skills {
-        state
-            .skills_index
-            .entry(skill.name.to_lowercase())
-            .or_default()
-            .insert(agent_id.clone());
-    }
-
-    // Store the agent definition
-    state.db_agents.insert(agent_id, agent_def);
-
-    (StatusCode::CREATED, "Agent registered successfully")
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<RegisterParams>,
+    Json(registration): Json<Registration<AgentDefinition>>,
+) -> (StatusCode, Json<RegistryResponse>) {
+    info!("Received register request for agent: {}", registration.definition.name);
+    into_registry_response(StatusCode::CREATED, state.register(registration, params.strict, &principal, &origin))
 }
 
 /// Deregisters an AgentDefinition and removes it from the skills index.
+/// Rejected with 409 Conflict if other entries depend on it, unless force=true is given.
 async fn deregister_agent_definition(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<DeregisterParams>,
     Json(agent_def): Json<AgentDefinition>,
-) -> impl IntoResponse {
+) -> (StatusCode, Json<RegistryResponse>) {
     info!("Received deregister request for agent: {}", agent_def.name);
-    let agent_id = &agent_def.id;
-    let agent_skills = agent_def.skills.clone();
-
-    // Remove the agent from the skills index
-    for skill in agent_skills {
-        let skill_key = skill.name.to_lowercase();
-        if let Some(mut agents_with_skill) = state.skills_index.get_mut(&skill_key) {
-            agents_with_skill.remove(agent_id);
-            // Clean up the skill entry if no agents are left
-            if agents_with_skill.is_empty() {
-                state.skills_index.remove(&skill_key);
-            }
-        }
-    }
-
-    // Remove the agent from the main database
-    state.db_agents.remove(agent_id);
-
-    (StatusCode::OK, "Agent deregistered successfully")
+    into_registry_response(StatusCode::OK, state.deregister::<AgentDefinition>(&agent_def.id, params.force, &principal, &origin))
 }
 
 /// Lists all currently registered AgentDefinitions.
-async fn list_agent_definitions(State(state): State<AppState>) -> Json<Vec<AgentDefinition>> {
-    let list_agents: Vec<AgentDefinition> = state.db_agents.iter().map(|e| e.value().clone()).collect();
-    Json(list_agents)
+/// An optional label selector filters the agents, e.g. /agents?selector=env=prod
+/// Supports conditional requests: If-None-Match with the ETag of a previous response gives 304.
+async fn list_agent_definitions(
+    State(state): State<AppState>,
+    Query(params): Query<SelectorParams>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let selector = params.parse()?;
+    let list_agents: Vec<AgentDefinition> = state.select_agents(&selector);
+    conditional_json(&headers, &list_agents)
+}
+
+/// Returns a registered agent definition, 404 if the agent is not registered.
+async fn get_agent_definition(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> Result<Json<AgentDefinition>, (StatusCode, String)> {
+    match state.db_agents.get(&agent_id) {
+        Some(entry) => Ok(Json(entry.value().clone())),
+        None => Err((StatusCode::NOT_FOUND, format!("Agent '{}' is not registered", agent_id))),
+    }
 }
 
 /// Searches for agents possessing a specific skill.
 /// The skill is provided as a query parameter, e.g., /agents/search?skill=math
+/// An optional label selector filters the agents, e.g. /agents/search?skill=math&selector=env=prod
+/// With sort=reputation, the agents best rated by the evaluation service come first.
 async fn search_agents_by_skill(
     State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<Json<Vec<AgentDefinition>>, StatusCode> {
+    let selector = parse_selector(params.get("selector").map(|s| s.as_str())).map_err(|(status, _)| status)?;
+    let sort = match params.get("sort") {
+        Some(sort) => sort.parse::<SortOrder>().map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => SortOrder::default(),
+    };
+
     // Get the skill from the query parameters
     let skill = match params.get("skill") {
         Some(s) => s.to_lowercase(),
@@ -163,15 +306,13 @@ async fn search_agents_by_skill(
 
     info!("Received search request for skill: {}", skill);
 
-    let mut found_agents = Vec::new();
-    // Look up the skill in the index
-    if let Some(agent_ids) = state.skills_index.get(&skill) {
-        // Retrieve the full AgentDefinition for each matching agent ID
-        for id in agent_ids.iter() {
-            if let Some(agent_def) = state.db_agents.get(id) {
-                found_agents.push(agent_def.value().clone());
-            }
-        }
+    let mut found_agents = state.agents_with_skill(&skill, &selector);
+    if sort == SortOrder::Reputation {
+        found_agents.sort_by(|a, b| {
+            let reputation_a = state.reputation_of(ResourceKind::Agent, &a.id);
+            let reputation_b = state.reputation_of(ResourceKind::Agent, &b.id);
+            reputation_b.partial_cmp(&reputation_a).unwrap_or(std::cmp::Ordering::Equal)
+        });
     }
 
     info!("Found {} agents with skill '{}'", found_agents.len(), skill);
@@ -181,41 +322,91 @@ async fn search_agents_by_skill(
 /// Registers a TaskDefinition.
 async fn register_task_definition(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<RegisterParams>,
+    Json(registration): Json<Registration<TaskDefinition>>,
+) -> (StatusCode, Json<RegistryResponse>) {
+    info!("Received register request for task: {}", registration.definition.name);
+    into_registry_response(StatusCode::CREATED, state.register(registration, params.strict, &principal, &origin))
+}
+
+/// Deregisters a TaskDefinition.
+/// Rejected with 409 Conflict if other entries depend on it, unless force=true is given.
+async fn deregister_task_definition(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<DeregisterParams>,
     Json(task_def): Json<TaskDefinition>,
-) -> impl IntoResponse {
-    info!("Received register request for task: {}", task_def.name);
-    state.db_tasks.insert(task_def.id.clone(), task_def);
-    (StatusCode::CREATED, "Task registered successfully")
+) -> (StatusCode, Json<RegistryResponse>) {
+    info!("Received deregister request for task: {}", task_def.name);
+    into_registry_response(StatusCode::OK, state.deregister::<TaskDefinition>(&task_def.id, params.force, &principal, &origin))
 }
 
 /// Lists all currently registered TaskDefinitions.
-async fn list_task_definitions(State(state): State<AppState>) -> Json<Vec<TaskDefinition>> {
-    let list_tasks: Vec<TaskDefinition> = state.db_tasks.iter().map(|e| e.value().clone()).collect();
-    Json(list_tasks)
+/// An optional label selector filters the tasks, e.g. /tasks?selector=team=billing
+/// Supports conditional requests, see list_agent_definitions.
+async fn list_task_definitions(
+    State(state): State<AppState>,
+    Query(params): Query<SelectorParams>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let selector = params.parse()?;
+    let list_tasks: Vec<TaskDefinition> = state.select_tasks(&selector);
+    conditional_json(&headers, &list_tasks)
 }
 
 /// Registers a ToolDefinition.
+/// Near-duplicates of the tool are reported as warnings, or rejected with strict=true.
 async fn register_tool_definition(
     State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<RegisterParams>,
+    Json(registration): Json<Registration<ToolDefinition>>,
+) -> (StatusCode, Json<RegistryResponse>) {
+    info!("Received register request for tool: {}", registration.definition.name);
+    into_registry_response(StatusCode::CREATED, state.register(registration, params.strict, &principal, &origin))
+}
+
+/// Deregisters a ToolDefinition.
+/// Rejected with 409 Conflict if other entries depend on it, unless force=true is given.
+async fn deregister_tool_definition(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    origin: RequestOrigin,
+    Query(params): Query<DeregisterParams>,
     Json(tool_def): Json<ToolDefinition>,
-) -> impl IntoResponse {
-    info!("Received register request for tool: {}", tool_def.name);
-    state.db_tools.insert(tool_def.id.clone(), tool_def);
-    (StatusCode::CREATED, "Tool registered successfully")
+) -> (StatusCode, Json<RegistryResponse>) {
+    info!("Received deregister request for tool: {}", tool_def.name);
+    into_registry_response(StatusCode::OK, state.deregister::<ToolDefinition>(&tool_def.id, params.force, &principal, &origin))
 }
 
 /// Lists all currently registered ToolDefinitions.
-async fn list_tool_definitions(State(state): State<AppState>) -> Json<Vec<ToolDefinition>> {
-    let list_tools: Vec<ToolDefinition> = state.db_tools.iter().map(|e| e.value().clone()).collect();
-    Json(list_tools)
+/// An optional label selector filters the tools, e.g. /tools?selector=tier in (free,standard)
+/// Supports conditional requests, see list_agent_definitions.
+async fn list_tool_definitions(
+    State(state): State<AppState>,
+    Query(params): Query<SelectorParams>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let selector = params.parse()?;
+    let list_tools: Vec<ToolDefinition> = state.select_tools(&selector);
+    conditional_json(&headers, &list_tools)
 }
 
-async fn list_available_resources(State(state): State<AppState>) -> impl IntoResponse {
+/// Describes all available resources, optionally filtered by a label selector.
+async fn list_available_resources(
+    State(state): State<AppState>,
+    Query(params): Query<SelectorParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let selector = params.parse()?;
     let mut available_resources = String::new();
 
-    let list_agents: Vec<AgentDefinition> = state.db_agents.iter().map(|e| e.value().clone()).collect();
-    let list_tools: Vec<ToolDefinition> = state.db_tools.iter().map(|e| e.value().clone()).collect();
-    let list_tasks: Vec<TaskDefinition> = state.db_tasks.iter().map(|e| e.value().clone()).collect();
+    let list_agents: Vec<AgentDefinition> = state.select_agents(&selector);
+    let list_tools: Vec<ToolDefinition> = state.select_tools(&selector);
+    let list_tasks: Vec<TaskDefinition> = state.select_tasks(&selector);
 
     if !list_tools.is_empty() {
         let tool_details = list_tools.iter()
@@ -244,5 +435,5 @@ async fn list_available_resources(State(state): State<AppState>) -> impl IntoRes
         available_resources.push('\n');
     }
 
-    (StatusCode::OK, Json(available_resources))
+    Ok((StatusCode::OK, Json(available_resources)))
 }
\ No newline at end of file
@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::audit::RequestOrigin;
+use crate::discovery_server::auth::OwnedEntry;
+use crate::discovery_server::dependencies::DependencyEdge;
+use crate::discovery_server::labels::LabeledEntry;
+use crate::discovery_server::registry::ResourceKey;
+use crate::discovery_server::server::AppState;
+
+/// Version of the snapshot document produced by this server.
+/// Bump it whenever the layout of RegistrySnapshot changes in an incompatible way.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// A point-in-time export of the whole registry, used for backups and migrations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    /// Format version of the document, see SNAPSHOT_FORMAT_VERSION.
+    pub version: u32,
+    /// RFC 3339 timestamp of the export.
+    pub exported_at: String,
+    pub agents: Vec<AgentDefinition>,
+    pub tasks: Vec<TaskDefinition>,
+    pub tools: Vec<ToolDefinition>,
+    /// Dependencies declared between the entries.
+    #[serde(default)]
+    pub dependencies: Vec<DependencyEdge>,
+    /// Labels attached to the entries.
+    #[serde(default)]
+    pub labels: Vec<LabeledEntry>,
+    /// Principals owning the entries, so that ownership survives a restore.
+    #[serde(default)]
+    pub owners: Vec<OwnedEntry>,
+    /// Secondary indexes at export time. They are informational only:
+    /// on import, indexes are rebuilt from the definitions.
+    #[serde(default)]
+    pub indexes: SnapshotIndexes,
+}
+
+/// Indexes exported alongside the definitions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotIndexes {
+    /// Key: skill name (lowercase), Value: ids of the agents having this skill.
+    pub skills: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// How an imported snapshot is combined with the current registry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Upsert the snapshot entries, keeping entries that are not in the snapshot.
+    #[default]
+    Merge,
+    /// Clear the registry before loading the snapshot.
+    Replace,
+}
+
+/// Query parameters accepted by the snapshot import endpoint, e.g. /admin/snapshot?mode=replace
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+/// Outcome of a snapshot import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub mode: ImportMode,
+    pub agents: usize,
+    pub tasks: usize,
+    pub tools: usize,
+}
+
+impl AppState {
+    /// Exports all agents, tasks, tools and indexes as a single document.
+    pub fn export_snapshot(&self) -> RegistrySnapshot {
+        let skills = self
+            .skills_index
+            .iter()
+            .map(|e| (e.key().clone(), e.value().iter().cloned().collect()))
+            .collect();
+
+        RegistrySnapshot {
+            version: SNAPSHOT_FORMAT_VERSION,
+            exported_at: Utc::now().to_rfc3339(),
+            agents: self.db_agents.iter().map(|e| e.value().clone()).collect(),
+            tasks: self.db_tasks.iter().map(|e| e.value().clone()).collect(),
+            tools: self.db_tools.iter().map(|e| e.value().clone()).collect(),
+            dependencies: self.dependency_edges(),
+            labels: self.labeled_entries(),
+            owners: self.owned_entries(),
+            indexes: SnapshotIndexes { skills },
+        }
+    }
+
+    /// Loads a snapshot into the registry, rebuilding the indexes from the definitions.
+    /// Entries that are managed by the static configuration directory are left untouched.
+    pub fn import_snapshot(&self, snapshot: RegistrySnapshot, mode: ImportMode, origin: &RequestOrigin) -> anyhow::Result<ImportSummary> {
+        if snapshot.version > SNAPSHOT_FORMAT_VERSION {
+            anyhow::bail!(
+                "Unsupported snapshot version {} (this server supports up to {})",
+                snapshot.version,
+                SNAPSHOT_FORMAT_VERSION
+            );
+        }
+
+        if mode == ImportMode::Replace {
+            self.clear_registry(origin);
+        }
+
+        let summary = ImportSummary {
+            mode,
+            agents: snapshot.agents.len(),
+            tasks: snapshot.tasks.len(),
+            tools: snapshot.tools.len(),
+        };
+
+        for agent_def in snapshot.agents {
+            let key = ResourceKey::agent(agent_def.id.as_str());
+            if !self.is_static(&key) {
+                let previous = self.upsert_agent(agent_def.clone());
+                self.audit_change(&key, origin, previous.as_ref(), Some(&agent_def));
+            }
+        }
+        for task_def in snapshot.tasks {
+            let key = ResourceKey::task(task_def.id.as_str());
+            if !self.is_static(&key) {
+                let previous = self.upsert_task(task_def.clone());
+                self.audit_change(&key, origin, previous.as_ref(), Some(&task_def));
+            }
+        }
+        for tool_def in snapshot.tools {
+            let key = ResourceKey::tool(tool_def.id.as_str());
+            if !self.is_static(&key) {
+                let previous = self.upsert_tool(tool_def.clone());
+                self.audit_change(&key, origin, previous.as_ref(), Some(&tool_def));
+            }
+        }
+
+        for edge in snapshot.dependencies {
+            if !self.is_static(&edge.from) && self.contains(&edge.from) {
+                self.dependencies.entry(edge.from).or_default().insert(edge.to);
+            }
+        }
+
+        for entry in snapshot.labels {
+            if !self.is_static(&entry.key) && self.contains(&entry.key) {
+                self.set_labels(&entry.key, entry.labels);
+            }
+        }
+
+        for entry in snapshot.owners {
+            if !self.is_static(&entry.key) && self.contains(&entry.key) {
+                self.owners.insert(entry.key, entry.owner);
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Reads a snapshot from a JSON file and merges it into the registry.
+    pub fn load_snapshot_file(&self, path: impl AsRef<Path>) -> anyhow::Result<ImportSummary> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let snapshot: RegistrySnapshot = serde_json::from_str(&content)?;
+        self.import_snapshot(snapshot, ImportMode::Merge, &RequestOrigin::system("seed-file"))
+    }
+}
+
+/// Exports the registry as a versioned JSON document.
+pub async fn export_snapshot(State(state): State<AppState>) -> Json<RegistrySnapshot> {
+    info!("Received snapshot export request");
+    Json(state.export_snapshot())
+}
+
+/// Imports a snapshot previously produced by export_snapshot.
+/// The mode is provided as a query parameter, e.g. /admin/snapshot?mode=replace (default: merge)
+pub async fn import_snapshot(
+    State(state): State<AppState>,
+    Query(params): Query<ImportParams>,
+    origin: RequestOrigin,
+    Json(snapshot): Json<RegistrySnapshot>,
+) -> Result<Json<ImportSummary>, (StatusCode, String)> {
+    info!("Received snapshot import request with mode: {:?}", params.mode);
+
+    match state.import_snapshot(snapshot, params.mode, &origin) {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => {
+            let error_message = format!("Failed to import snapshot: {}", e);
+            warn!("{}", error_message);
+            Err((StatusCode::BAD_REQUEST, error_message))
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_server::audit::RequestOrigin;
+use crate::discovery_server::labels::{validate_labels, Labels};
+use crate::discovery_server::registry::{Registration, ResourceKey, ResourceKind};
+use crate::discovery_server::server::AppState;
+
+/*
+static_config.rs
+loads declarative registrations from a directory of TOML, JSON or YAML files.
+
+Each file may declare any number of agents, tasks and tools, e.g. in TOML:
+
+    [[tools]]
+    id = "web_search"
+    name = "Web Search"
+    depends_on = ["agent:search_agent"]
+    ...
+
+The directory is polled for changes: added, modified and removed files are
+hot-applied to the registry. Entries loaded this way are marked as static and
+cannot be modified or deregistered over HTTP.
+*/
+
+/// Content of a single file of the static configuration directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StaticRegistryFile {
+    #[serde(default)]
+    pub agents: Vec<Registration<AgentDefinition>>,
+    #[serde(default)]
+    pub tasks: Vec<Registration<TaskDefinition>>,
+    #[serde(default)]
+    pub tools: Vec<Registration<ToolDefinition>>,
+}
+
+impl StaticRegistryFile {
+    /// Parses a file, the format being selected from its extension.
+    /// Returns None for files that do not have a supported extension.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        let parsed = match extension.as_deref() {
+            Some("toml") => toml::from_str(&std::fs::read_to_string(path)?)?,
+            Some("json") => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&std::fs::read_to_string(path)?)?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(parsed))
+    }
+}
+
+/// A definition declared by the static configuration directory.
+#[derive(Debug, Clone)]
+enum StaticDefinition {
+    Agent(AgentDefinition),
+    Task(TaskDefinition),
+    Tool(ToolDefinition),
+}
+
+impl StaticDefinition {
+    fn to_json(&self) -> Option<serde_json::Value> {
+        match self {
+            StaticDefinition::Agent(def) => serde_json::to_value(def).ok(),
+            StaticDefinition::Task(def) => serde_json::to_value(def).ok(),
+            StaticDefinition::Tool(def) => serde_json::to_value(def).ok(),
+        }
+    }
+}
+
+/// An entry declared by the static configuration directory, with its metadata and source file.
+#[derive(Debug, Clone)]
+struct DesiredEntry {
+    definition: StaticDefinition,
+    depends_on: Vec<String>,
+    labels: Labels,
+    path: PathBuf,
+}
+
+impl DesiredEntry {
+    fn new<T>(registration: Registration<T>, definition: fn(T) -> StaticDefinition, path: &Path) -> Self {
+        DesiredEntry {
+            definition: definition(registration.definition),
+            // The static configuration is declarative, no depends_on means no dependency
+            depends_on: registration.depends_on.unwrap_or_default(),
+            labels: registration.labels.unwrap_or_default(),
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Watches a directory of static registrations and keeps the registry in sync with it.
+pub struct StaticConfigWatcher {
+    dir: PathBuf,
+    poll_interval: Duration,
+    /// Path, modification time and size of every file seen during the last scan.
+    fingerprint: Vec<(PathBuf, Option<SystemTime>, u64)>,
+}
+
+impl StaticConfigWatcher {
+    pub fn new(dir: impl Into<PathBuf>, poll_interval: Duration) -> Self {
+        StaticConfigWatcher {
+            dir: dir.into(),
+            poll_interval,
+            fingerprint: Vec::new(),
+        }
+    }
+
+    /// Loads the directory once, then spawns a background task polling it for changes.
+    pub fn spawn(mut self, state: AppState) -> anyhow::Result<tokio::task::JoinHandle<()>> {
+        self.sync(&state)?;
+
+        Ok(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sync(&state) {
+                    warn!("Failed to reload static configuration from {:?}: {}", self.dir, e);
+                }
+            }
+        }))
+    }
+
+    /// Applies the content of the directory to the registry if any file changed since the last call.
+    pub fn sync(&mut self, state: &AppState) -> anyhow::Result<()> {
+        let files = self.list_files()?;
+        let fingerprint = files
+            .iter()
+            .map(|path| {
+                let metadata = std::fs::metadata(path).ok();
+                let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+                let len = metadata.map(|m| m.len()).unwrap_or_default();
+                (path.clone(), modified, len)
+            })
+            .collect::<Vec<_>>();
+
+        if fingerprint == self.fingerprint {
+            return Ok(());
+        }
+
+        // Parse every file before touching the registry, so that a broken file does not
+        // remove the entries it used to declare.
+        let mut desired: HashMap<ResourceKey, DesiredEntry> = HashMap::new();
+        for path in &files {
+            let Some(file) = StaticRegistryFile::load(path)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+            else {
+                continue;
+            };
+            for registration in file.agents {
+                let key = ResourceKey::agent(registration.definition.id.as_str());
+                desired.insert(key, DesiredEntry::new(registration, StaticDefinition::Agent, path));
+            }
+            for registration in file.tasks {
+                let key = ResourceKey::task(registration.definition.id.as_str());
+                desired.insert(key, DesiredEntry::new(registration, StaticDefinition::Task, path));
+            }
+            for registration in file.tools {
+                let key = ResourceKey::tool(registration.definition.id.as_str());
+                desired.insert(key, DesiredEntry::new(registration, StaticDefinition::Tool, path));
+            }
+        }
+
+        apply_static_definitions(state, desired);
+        self.fingerprint = fingerprint;
+        Ok(())
+    }
+
+    fn list_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Makes the static entries of the registry match the desired set.
+fn apply_static_definitions(state: &AppState, desired: HashMap<ResourceKey, DesiredEntry>) {
+    // Remove the static entries that disappeared from the directory
+    let removed: Vec<ResourceKey> = state
+        .static_entries
+        .iter()
+        .map(|e| e.key().clone())
+        .filter(|key| !desired.contains_key(key))
+        .collect();
+
+    let origin = RequestOrigin::system("static-config");
+
+    for key in removed {
+        info!("Removing static registration {}", key);
+        for dependent in state.dependents_of(&key) {
+            warn!("Removing static registration {} orphans {}", key, dependent);
+        }
+        state.static_entries.remove(&key);
+        match key.kind {
+            ResourceKind::Agent => {
+                let removed = state.remove_agent(&key.id);
+                state.audit_change(&key, &origin, removed.as_ref(), None);
+            }
+            ResourceKind::Task => {
+                let removed = state.remove_task(&key.id);
+                state.audit_change(&key, &origin, removed.as_ref(), None);
+            }
+            ResourceKind::Tool => {
+                let removed = state.remove_tool(&key.id);
+                state.audit_change(&key, &origin, removed.as_ref(), None);
+            }
+        }
+    }
+
+    // Add new entries and update the modified ones
+    for (key, entry) in &desired {
+        if state.is_static(key) && current_definition(state, key) == entry.definition.to_json() {
+            continue;
+        }
+
+        info!("Applying static registration {} from {}", key, entry.path.display());
+        match entry.definition.clone() {
+            StaticDefinition::Agent(def) => {
+                let previous = state.upsert_agent(def.clone());
+                state.audit_change(key, &origin, previous.as_ref(), Some(&def));
+            }
+            StaticDefinition::Task(def) => {
+                let previous = state.upsert_task(def.clone());
+                state.audit_change(key, &origin, previous.as_ref(), Some(&def));
+            }
+            StaticDefinition::Tool(def) => {
+                let previous = state.upsert_tool(def.clone());
+                state.audit_change(key, &origin, previous.as_ref(), Some(&def));
+            }
+        }
+        state.static_entries.insert(key.clone(), entry.path.clone());
+    }
+
+    // Labels and dependencies are set once every static entry is registered
+    for (key, entry) in &desired {
+        if let Err(e) = validate_labels(&entry.labels) {
+            warn!("Ignoring labels of static registration {} from {}: {}", key, entry.path.display(), e);
+        } else if state.labels_of(key) != entry.labels {
+            state.set_labels(key, entry.labels.clone());
+        }
+        for warning in state.set_dependencies(key, &entry.depends_on) {
+            warn!("Static registration from {}: {}", entry.path.display(), warning);
+        }
+    }
+}
+
+fn current_definition(state: &AppState, key: &ResourceKey) -> Option<serde_json::Value> {
+    match key.kind {
+        ResourceKind::Agent => state.db_agents.get(&key.id).and_then(|e| serde_json::to_value(e.value()).ok()),
+        ResourceKind::Task => state.db_tasks.get(&key.id).and_then(|e| serde_json::to_value(e.value()).ok()),
+        ResourceKind::Tool => state.db_tools.get(&key.id).and_then(|e| serde_json::to_value(e.value()).ok()),
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::discovery_server::audit::{AuditAction, AuditRecord};
+use crate::discovery_server::auth::Principal;
+use crate::discovery_server::registry::ResourceKind;
+use crate::discovery_server::server::AppState;
+
+/*
+webhooks.rs
+notifies external consumers of registry changes by POSTing them to webhook subscriptions.
+
+Every change recorded in the audit log is delivered, as JSON, to the subscriptions whose
+filter matches it. When the subscription has a secret, the payload is signed with
+HMAC-SHA256 and the signature sent in the x-swarm-signature header ("sha256=<hex>"),
+so that receivers can check that the payload comes from the discovery service.
+
+Failed deliveries are retried with exponential backoff; deliveries still failing after
+the last attempt are kept in a dead-letter list, viewable through /admin/webhooks/dead-letters.
+*/
+
+/// Header carrying the HMAC-SHA256 signature of the payload.
+pub const SIGNATURE_HEADER: &str = "x-swarm-signature";
+/// Header carrying the event type, e.g. "agent.register".
+pub const EVENT_HEADER: &str = "x-swarm-event";
+/// Header carrying the unique id of a delivery, identical across its retries.
+pub const DELIVERY_HEADER: &str = "x-swarm-delivery";
+
+/// Maximum number of dead letters kept, the oldest being dropped first.
+const MAX_DEAD_LETTERS: usize = 1000;
+
+/// Retry policy of the webhook deliveries.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Number of delivery attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled at every retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two retries.
+    pub max_backoff: Duration,
+    /// Timeout of a single delivery attempt.
+    pub request_timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Delay before the given retry (1 for the first retry).
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Events a subscription is interested in. Empty lists match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookFilter {
+    #[serde(default)]
+    pub kinds: Vec<ResourceKind>,
+    #[serde(default)]
+    pub actions: Vec<AuditAction>,
+    #[serde(default)]
+    pub entity_ids: Vec<String>,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&record.kind))
+            && (self.actions.is_empty() || self.actions.contains(&record.action))
+            && (self.entity_ids.is_empty() || self.entity_ids.contains(&record.entity_id))
+    }
+}
+
+/// Request creating a webhook subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscriptionRequest {
+    pub url: String,
+    #[serde(default)]
+    pub filter: WebhookFilter,
+    /// Secret used to sign the payloads. Payloads are not signed when missing.
+    pub secret: Option<String>,
+}
+
+/// A webhook subscription. The secret is never returned by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: String,
+    pub url: String,
+    pub filter: WebhookFilter,
+    #[serde(skip)]
+    pub secret: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body POSTed to the webhook subscriptions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub delivery_id: String,
+    pub subscription_id: String,
+    /// Event type, e.g. "agent.register".
+    pub event: String,
+    pub record: AuditRecord,
+}
+
+/// A delivery that failed after every attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub delivery_id: String,
+    pub subscription_id: String,
+    pub url: String,
+    pub payload: WebhookPayload,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Webhook subscriptions and dead letters of the discovery server.
+#[derive(Debug, Default)]
+pub struct WebhookRegistry {
+    pub config: WebhookConfig,
+    subscriptions: DashMap<String, WebhookSubscription>,
+    dead_letters: RwLock<VecDeque<DeadLetter>>,
+    client: reqwest::Client,
+}
+
+impl WebhookRegistry {
+    pub fn new(config: WebhookConfig) -> Self {
+        WebhookRegistry {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn subscribe(&self, request: WebhookSubscriptionRequest, created_by: &str) -> anyhow::Result<WebhookSubscription> {
+        let url = reqwest::Url::parse(&request.url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            anyhow::bail!("Unsupported webhook URL scheme: {}", url.scheme());
+        }
+
+        let subscription = WebhookSubscription {
+            id: Uuid::new_v4().to_string(),
+            url: request.url,
+            filter: request.filter,
+            secret: request.secret.filter(|secret| !secret.is_empty()),
+            created_by: created_by.to_string(),
+            created_at: Utc::now(),
+        };
+        self.subscriptions.insert(subscription.id.clone(), subscription.clone());
+        Ok(subscription)
+    }
+
+    pub fn unsubscribe(&self, subscription_id: &str) -> Option<WebhookSubscription> {
+        self.subscriptions.remove(subscription_id).map(|(_, subscription)| subscription)
+    }
+
+    pub fn subscriptions(&self) -> Vec<WebhookSubscription> {
+        let mut subscriptions: Vec<WebhookSubscription> = self.subscriptions.iter().map(|e| e.value().clone()).collect();
+        subscriptions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        subscriptions
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes every dead letter, returning how many were dropped.
+    pub fn clear_dead_letters(&self) -> usize {
+        let mut dead_letters = self.dead_letters.write().unwrap();
+        let count = dead_letters.len();
+        dead_letters.clear();
+        count
+    }
+
+    fn push_dead_letter(&self, dead_letter: DeadLetter) {
+        let mut dead_letters = self.dead_letters.write().unwrap();
+        if dead_letters.len() == MAX_DEAD_LETTERS {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(dead_letter);
+    }
+
+    /// Starts delivering the registry changes to the subscriptions.
+    pub fn spawn_dispatcher(self: std::sync::Arc<Self>, mut changes: broadcast::Receiver<AuditRecord>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match changes.recv().await {
+                    Ok(record) => self.dispatch(&record),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Webhook dispatcher lagged behind the registry changes, {} events not delivered", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+
+    /// Starts a delivery of the record to every matching subscription.
+    fn dispatch(self: &std::sync::Arc<Self>, record: &AuditRecord) {
+        let event = event_type(record);
+        for subscription in self.subscriptions.iter().filter(|e| e.value().filter.matches(record)) {
+            let payload = WebhookPayload {
+                delivery_id: Uuid::new_v4().to_string(),
+                subscription_id: subscription.id.clone(),
+                event: event.clone(),
+                record: record.clone(),
+            };
+            let registry = self.clone();
+            let subscription = subscription.value().clone();
+            tokio::spawn(async move { registry.deliver(subscription, payload).await });
+        }
+    }
+
+    /// Delivers a payload, retrying with exponential backoff, and dead-letters it on failure.
+    async fn deliver(&self, subscription: WebhookSubscription, payload: WebhookPayload) {
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook payload {}: {}", payload.delivery_id, e);
+                return;
+            }
+        };
+        let signature = subscription.secret.as_deref().map(|secret| sign(secret, &body));
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.post(&subscription.url, &payload, &body, signature.as_deref()).await {
+                Ok(()) => {
+                    info!("Delivered {} to webhook {} (attempt {})", payload.event, subscription.id, attempt);
+                    return;
+                }
+                Err(e) if attempt < self.config.max_attempts => {
+                    let delay = self.config.backoff(attempt);
+                    warn!(
+                        "Webhook delivery {} to {} failed (attempt {}): {}, retrying in {:?}",
+                        payload.delivery_id, subscription.url, attempt, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    warn!(
+                        "Webhook delivery {} to {} failed after {} attempts: {}",
+                        payload.delivery_id, subscription.url, attempt, e
+                    );
+                    self.push_dead_letter(DeadLetter {
+                        delivery_id: payload.delivery_id.clone(),
+                        subscription_id: subscription.id.clone(),
+                        url: subscription.url.clone(),
+                        payload,
+                        attempts: attempt,
+                        last_error: e.to_string(),
+                        failed_at: Utc::now(),
+                    });
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn post(&self, url: &str, payload: &WebhookPayload, body: &[u8], signature: Option<&str>) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(url)
+            .timeout(self.config.request_timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &payload.event)
+            .header(DELIVERY_HEADER, &payload.delivery_id)
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Event type of a record, e.g. "agent.register".
+fn event_type(record: &AuditRecord) -> String {
+    let action = match record.action {
+        AuditAction::Register => "register",
+        AuditAction::Update => "update",
+        AuditAction::Deregister => "deregister",
+    };
+    format!("{}.{}", record.kind, action)
+}
+
+/// Signs a payload with HMAC-SHA256, formatted as "sha256=<hex>".
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Creates a webhook subscription.
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<WebhookSubscriptionRequest>,
+) -> Result<(StatusCode, Json<WebhookSubscription>), (StatusCode, String)> {
+    info!("Received webhook subscription request for {}", request.url);
+    state
+        .webhooks
+        .subscribe(request, &principal.id)
+        .map(|subscription| (StatusCode::CREATED, Json(subscription)))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+/// Lists the webhook subscriptions.
+pub async fn list_webhooks(State(state): State<AppState>) -> Json<Vec<WebhookSubscription>> {
+    Json(state.webhooks.subscriptions())
+}
+
+/// Deletes a webhook subscription.
+pub async fn delete_webhook(State(state): State<AppState>, Path(subscription_id): Path<String>) -> (StatusCode, String) {
+    info!("Received webhook deletion request for {}", subscription_id);
+    match state.webhooks.unsubscribe(&subscription_id) {
+        Some(_) => (StatusCode::OK, "Webhook deleted successfully".to_string()),
+        None => (StatusCode::NOT_FOUND, format!("Unknown webhook: {}", subscription_id)),
+    }
+}
+
+/// Lists the deliveries that failed after every attempt, oldest first.
+pub async fn list_dead_letters(State(state): State<AppState>) -> Json<Vec<DeadLetter>> {
+    Json(state.webhooks.dead_letters())
+}
+
+/// Drops the dead letters.
+pub async fn clear_dead_letters(State(state): State<AppState>) -> (StatusCode, String) {
+    let count = state.webhooks.clear_dead_letters();
+    (StatusCode::OK, format!("Dropped {} dead letters", count))
+}
@@ -1,8 +1,23 @@
-use reqwest::{Client, Error};
-use anyhow::Result;
+use std::time::Duration;
+
+use reqwest::{header, Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use tracing::warn;
 //use crate::model::models::{AgentDefinition, TaskDefinition, ToolDefinition};
 use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
 
+use crate::discovery_server::audit::{AuditQuery, AuditRecord};
+use crate::discovery_server::auth::API_KEY_HEADER;
+use crate::discovery_server::dependencies::DependencyGraph;
+use crate::discovery_server::matching::TaskCandidate;
+use crate::discovery_server::labels::LabeledEntry;
+use crate::discovery_server::registry::{Registration, RegistryResponse, ResourceKind};
+use crate::discovery_server::reputation::{AgentReputation, AgentScoreReport, SortOrder};
+use crate::discovery_server::search::SearchHit;
+use crate::discovery_server::webhooks::{DeadLetter, WebhookSubscription, WebhookSubscriptionRequest};
+use crate::discovery_server::snapshot::{ImportMode, ImportSummary, RegistrySnapshot};
+use crate::discovery_service_client::error::DiscoveryClientError;
+
 
 /*
 agent_discovery_client.rs
@@ -13,58 +28,327 @@ This file contains the concrete client implementation for interacting directly
 with the Agent Discovery Service's HTTP API.
 It handles the low-level details of making network requests (using reqwest) to register, deregister,
 list, and search for agents, tasks, and tools. It knows the specific endpoints and data formats of the discovery service.
+
+Unsuccessful statuses are turned into typed DiscoveryClientError values. Idempotent calls
+(lookups, and registrations and deregistrations, which are upserts and removals) are retried
+with exponential backoff when they fail with a transient error.
 */
 
+pub type Result<T, E = DiscoveryClientError> = std::result::Result<T, E>;
+
+/// Credentials sent to the discovery service to authenticate registrations.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    /// API key, sent in the x-api-key header.
+    ApiKey(String),
+    /// JWT, sent as an Authorization bearer token.
+    Bearer(String),
+}
+
+/// Outcome of a conditional request.
+#[derive(Debug, Clone)]
+pub enum Conditional<T> {
+    /// The resource changed, along with its new ETag.
+    Modified { value: T, etag: Option<String> },
+    /// The resource still has the ETag sent with the request.
+    NotModified,
+}
+
+/// Retry policy of the idempotent calls.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled at every retry.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// No retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before the given retry (1 for the first retry).
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Builder of AgentDiscoveryServiceClient, to configure timeouts, retries and credentials.
+#[derive(Debug, Clone)]
+pub struct AgentDiscoveryServiceClientBuilder {
+    discovery_service_url: String,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    retry_policy: RetryPolicy,
+    credentials: Option<Credentials>,
+}
+
+impl AgentDiscoveryServiceClientBuilder {
+    pub fn new(discovery_service_url: &str) -> Self {
+        AgentDiscoveryServiceClientBuilder {
+            discovery_service_url: discovery_service_url.trim_end_matches('/').to_string(),
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(30),
+            retry_policy: RetryPolicy::default(),
+            credentials: None,
+        }
+    }
+
+    /// Timeout of the connection to the discovery service.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Timeout of a whole request, from sending it to reading the response.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn build(self) -> Result<AgentDiscoveryServiceClient> {
+        let client = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .build()?;
+
+        Ok(AgentDiscoveryServiceClient {
+            discovery_service_url: self.discovery_service_url,
+            client,
+            credentials: self.credentials,
+            retry_policy: self.retry_policy,
+        })
+    }
+}
+
 /// A client for interacting with the Agent Discovery Service.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AgentDiscoveryServiceClient {
     discovery_service_url: String,
     client: Client,
+    credentials: Option<Credentials>,
+    retry_policy: RetryPolicy,
 }
 
 impl AgentDiscoveryServiceClient {
-    /// Creates a new client for the given discovery service URL.
+    /// Creates a new client for the given discovery service URL, with the default timeouts and retry policy.
     pub fn new(discovery_service_url: &str) -> Self {
-        AgentDiscoveryServiceClient {
-            discovery_service_url: discovery_service_url.to_string(),
-            client: Client::new(),
+        AgentDiscoveryServiceClientBuilder::new(discovery_service_url)
+            .build()
+            .unwrap_or_else(|e| {
+                warn!("Failed to configure the discovery client, falling back to the default one: {}", e);
+                AgentDiscoveryServiceClient {
+                    discovery_service_url: discovery_service_url.trim_end_matches('/').to_string(),
+                    client: Client::new(),
+                    credentials: None,
+                    retry_policy: RetryPolicy::default(),
+                }
+            })
+    }
+
+    /// Returns a builder to configure the timeouts, retries and credentials of the client.
+    pub fn builder(discovery_service_url: &str) -> AgentDiscoveryServiceClientBuilder {
+        AgentDiscoveryServiceClientBuilder::new(discovery_service_url)
+    }
+
+    /// Sets the credentials attached to every request.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// URL of the discovery service.
+    pub fn url(&self) -> &str {
+        &self.discovery_service_url
+    }
+
+    /// Prepares an authenticated request to a path of the discovery service.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.authenticate(self.client.request(method, format!("{}{}", self.discovery_service_url, path)))
+    }
+
+    /// Prepares an authenticated request to the path made of the given segments, each one
+    /// percent-encoded, so that ids containing '/', '?', '#' or spaces reach the right route.
+    fn request_segments(&self, method: Method, segments: &[&str]) -> Result<RequestBuilder> {
+        let mut url = Url::parse(&self.discovery_service_url)
+            .map_err(|e| DiscoveryClientError::InvalidUrl(format!("{}: {}", self.discovery_service_url, e)))?;
+        url.path_segments_mut()
+            .map_err(|_| DiscoveryClientError::InvalidUrl(self.discovery_service_url.clone()))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(self.authenticate(self.client.request(method, url)))
+    }
+
+    /// Attaches the credentials of the client to a request.
+    fn authenticate(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.credentials {
+            Some(Credentials::ApiKey(key)) => request.header(API_KEY_HEADER, key),
+            Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends a request once and turns an unsuccessful status into an error.
+    /// 304 Not Modified answers a conditional request, it is left to the caller.
+    async fn send(request: RequestBuilder) -> Result<Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() || status == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(DiscoveryClientError::from_response(status, body))
+    }
+
+    /// Sends a request, retrying idempotent ones on transient failures.
+    async fn execute(&self, request: RequestBuilder, idempotent: bool) -> Result<Response> {
+        let mut retry = 0;
+        loop {
+            // Streamed bodies cannot be cloned, such requests are only sent once
+            let attempt = match request.try_clone() {
+                Some(attempt) if idempotent => attempt,
+                _ => return Self::send(request).await,
+            };
+
+            match Self::send(attempt).await {
+                Err(e) if e.is_retryable() && retry < self.retry_policy.max_retries => {
+                    retry += 1;
+                    let delay = self.retry_policy.backoff(retry);
+                    warn!("Discovery service call failed: {}, retrying in {:?} ({}/{})", e, delay, retry, self.retry_policy.max_retries);
+                    tokio::time::sleep(delay).await;
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+
+    /// Sends a request and decodes its JSON response.
+    async fn execute_json<T: DeserializeOwned>(&self, request: RequestBuilder, idempotent: bool) -> Result<T> {
+        let response = self.execute(request, idempotent).await?;
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Sends a conditional request, the server answering 304 if its version still has the given ETag.
+    async fn execute_conditional<T: DeserializeOwned>(&self, mut request: RequestBuilder, etag: Option<&str>) -> Result<Conditional<T>> {
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
         }
+        let response = self.execute(request, true).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Conditional::NotModified);
+        }
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await?;
+        Ok(Conditional::Modified {
+            value: serde_json::from_slice(&body)?,
+            etag,
+        })
+    }
+
+    /// Sends a request and returns its response as text.
+    async fn execute_text(&self, request: RequestBuilder, idempotent: bool) -> Result<String> {
+        let response = self.execute(request, idempotent).await?;
+        Ok(response.text().await?)
+    }
+
+    /// Sends a registry operation and returns its message, as the service answered in text before.
+    /// The warnings of the response are logged, register_agent and alike return them.
+    async fn execute_registry_message(&self, request: RequestBuilder) -> Result<String> {
+        let response: RegistryResponse = self.execute_json(request, true).await?;
+        for warning in &response.warnings {
+            warn!("Discovery service warning: {}", warning);
+        }
+        Ok(response.message)
     }
 
     // Agent Definition methods
 
     /// Registers an agent definition with the discovery service.
-    pub async fn register_agent_definition(&self, agent_def: &AgentDefinition) -> Result<String, Error> {
-        let url = format!("{}/agents/register", self.discovery_service_url);
-        let response = self.client.post(&url).json(agent_def).send().await?;
-        response.text().await
+    pub async fn register_agent_definition(&self, agent_def: &AgentDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/agents/register").json(agent_def)).await
+    }
+
+    /// Registers an agent definition along with its registry metadata (dependencies, ...).
+    pub async fn register_agent(&self, registration: &Registration<AgentDefinition>) -> Result<RegistryResponse> {
+        self.execute_json(self.request(Method::POST, "/agents/register").json(registration), true).await
     }
-    
+
     /// Deregisters an agent definition from the discovery service.
-    pub async fn deregister_agent_definition(&self, agent_def: &AgentDefinition) -> Result<String, Error> {
-        let url = format!("{}/agents/deregister", self.discovery_service_url);
-        let response = self.client.post(&url).json(agent_def).send().await?;
-        response.text().await
+    pub async fn deregister_agent_definition(&self, agent_def: &AgentDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/agents/deregister").json(agent_def)).await
+    }
+
+    /// Returns a registered agent definition, or a NotFound error.
+    pub async fn get_agent_definition(&self, agent_id: &str) -> Result<AgentDefinition> {
+        self.execute_json(self.request_segments(Method::GET, &["agents", agent_id])?, true).await
+    }
+
+    /// Lists the agent definitions whose labels match the selector, e.g. "env=prod,team!=research".
+    pub async fn list_agent_definitions_matching(&self, selector: &str) -> Result<Vec<AgentDefinition>> {
+        self.execute_json(self.request(Method::GET, "/agents").query(&[("selector", selector)]), true).await
     }
 
     /// Lists all registered agent definitions.
-    pub async fn list_agent_definitions(&self) -> Result<Vec<AgentDefinition>, Error> {
-        let url = format!("{}/agents", self.discovery_service_url);
-        let response = self.client.get(&url).send().await?;
-        response.json::<Vec<AgentDefinition>>().await
+    pub async fn list_agent_definitions(&self) -> Result<Vec<AgentDefinition>> {
+        self.execute_json(self.request(Method::GET, "/agents"), true).await
+    }
+
+    /// Lists all registered agent definitions, unless they did not change since the response with the given ETag.
+    pub async fn list_agent_definitions_if_changed(&self, etag: Option<&str>) -> Result<Conditional<Vec<AgentDefinition>>> {
+        self.execute_conditional(self.request(Method::GET, "/agents"), etag).await
     }
 
     /// Searches for agents that have a specific skill.
     /// The skill is provided as a query parameter.
-    pub async fn search_agents_by_skill(&self, skill: &str) -> Result<Vec<AgentDefinition>, Error> {
-        let url = format!("{}/agents/search", self.discovery_service_url);
-        let response = self.client.get(&url).query(&[("skill", skill)]).send().await?;
-        response.json::<Vec<AgentDefinition>>().await
+    pub async fn search_agents_by_skill(&self, skill: &str) -> Result<Vec<AgentDefinition>> {
+        self.execute_json(self.request(Method::GET, "/agents/search").query(&[("skill", skill)]), true).await
+    }
+
+    /// Searches for agents that have a specific skill, best rated by the evaluation service first.
+    pub async fn search_agents_by_skill_and_reputation(&self, skill: &str) -> Result<Vec<AgentDefinition>> {
+        let request = self
+            .request(Method::GET, "/agents/search")
+            .query(&[("skill", skill), ("sort", "reputation")]);
+        self.execute_json(request, true).await
     }
 
     /// Lists all agents except for the one with the specified ID.
     /// This is useful for preventing an agent from discovering itself.
-    pub async fn list_other_agents_definitions(&self, agent_id_to_filter_out: &str) -> Result<Vec<AgentDefinition>, Error> {
+    pub async fn list_other_agents_definitions(&self, agent_id_to_filter_out: &str) -> Result<Vec<AgentDefinition>> {
         let all_agents = self.list_agent_definitions().await?;
         let filtered_agents = all_agents
             .into_iter()
@@ -76,39 +360,156 @@ impl AgentDiscoveryServiceClient {
     // Task Definition methods
 
     /// Registers a task definition with the discovery service.
-    pub async fn register_task_definition(&self, task_def: &TaskDefinition) -> Result<String, Error> {
-        let url = format!("{}/tasks/register", self.discovery_service_url);
-        let response = self.client.post(&url).json(task_def).send().await?;
-        response.text().await
+    pub async fn register_task_definition(&self, task_def: &TaskDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/tasks/register").json(task_def)).await
+    }
+
+    /// Registers a task definition along with its registry metadata (dependencies, ...).
+    pub async fn register_task(&self, registration: &Registration<TaskDefinition>) -> Result<RegistryResponse> {
+        self.execute_json(self.request(Method::POST, "/tasks/register").json(registration), true).await
+    }
+
+    /// Deregisters a task definition from the discovery service.
+    pub async fn deregister_task_definition(&self, task_def: &TaskDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/tasks/deregister").json(task_def)).await
+    }
+
+    /// Lists the task definitions whose labels match the selector, e.g. "env=prod,team!=research".
+    pub async fn list_task_definitions_matching(&self, selector: &str) -> Result<Vec<TaskDefinition>> {
+        self.execute_json(self.request(Method::GET, "/tasks").query(&[("selector", selector)]), true).await
     }
 
     /// Lists all registered task definitions.
-    pub async fn list_task_definitions(&self) -> Result<Vec<TaskDefinition>, Error> {
-        let url = format!("{}/tasks", self.discovery_service_url);
-        let response = self.client.get(&url).send().await?;
-        response.json::<Vec<TaskDefinition>>().await
+    pub async fn list_task_definitions(&self) -> Result<Vec<TaskDefinition>> {
+        self.execute_json(self.request(Method::GET, "/tasks"), true).await
+    }
+
+    /// Lists all registered task definitions, unless they did not change since the response with the given ETag.
+    pub async fn list_task_definitions_if_changed(&self, etag: Option<&str>) -> Result<Conditional<Vec<TaskDefinition>>> {
+        self.execute_conditional(self.request(Method::GET, "/tasks"), etag).await
+    }
+
+    /// Returns the agents and tools able to perform a registered task, best candidates first.
+    pub async fn find_task_candidates(&self, task_id: &str, limit: Option<usize>) -> Result<Vec<TaskCandidate>> {
+        let mut request = self.request_segments(Method::GET, &["tasks", task_id, "candidates"])?;
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.execute_json(request, true).await
     }
 
     // Tool Definition methods
 
     /// Registers a tool definition with the discovery service.
-    pub async fn register_tool_definition(&self, tool_def: &ToolDefinition) -> Result<String, Error> {
-        let url = format!("{}/tools/register", self.discovery_service_url);
-        let response = self.client.post(&url).json(tool_def).send().await?;
-        response.text().await
+    pub async fn register_tool_definition(&self, tool_def: &ToolDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/tools/register").json(tool_def)).await
+    }
+
+    /// Registers a tool definition along with its registry metadata (dependencies, ...).
+    pub async fn register_tool(&self, registration: &Registration<ToolDefinition>) -> Result<RegistryResponse> {
+        self.execute_json(self.request(Method::POST, "/tools/register").json(registration), true).await
+    }
+
+    /// Deregisters a tool definition from the discovery service.
+    pub async fn deregister_tool_definition(&self, tool_def: &ToolDefinition) -> Result<String> {
+        self.execute_registry_message(self.request(Method::POST, "/tools/deregister").json(tool_def)).await
+    }
+
+    /// Lists the tool definitions whose labels match the selector, e.g. "env=prod,team!=research".
+    pub async fn list_tool_definitions_matching(&self, selector: &str) -> Result<Vec<ToolDefinition>> {
+        self.execute_json(self.request(Method::GET, "/tools").query(&[("selector", selector)]), true).await
     }
 
     /// Lists all registered tool definitions.
-    pub async fn list_tool_definitions(&self) -> Result<Vec<ToolDefinition>, Error> {
-        let url = format!("{}/tools", self.discovery_service_url);
-        let response = self.client.get(&url).send().await?;
-        response.json::<Vec<ToolDefinition>>().await
+    pub async fn list_tool_definitions(&self) -> Result<Vec<ToolDefinition>> {
+        self.execute_json(self.request(Method::GET, "/tools"), true).await
+    }
+
+    /// Lists all registered tool definitions, unless they did not change since the response with the given ETag.
+    pub async fn list_tool_definitions_if_changed(&self, etag: Option<&str>) -> Result<Conditional<Vec<ToolDefinition>>> {
+        self.execute_conditional(self.request(Method::GET, "/tools"), etag).await
     }
-    
+
     /// Lists all available resources (agents, tools, and tasks).
-    pub async fn list_available_resources(&self) -> Result<String, Error> {
-        let url = format!("{}/resources", self.discovery_service_url);
-        let response = self.client.get(&url).send().await?;
-        response.text().await
+    pub async fn list_available_resources(&self) -> Result<String> {
+        // Returned as sent by the service, a JSON string
+        self.execute_text(self.request(Method::GET, "/resources"), true).await
+    }
+
+    /// Searches agents, tasks and tools matching a free text query, best hits first.
+    /// With SortOrder::Reputation, the relevance of the agents is weighted by their reputation.
+    pub async fn search(&self, query: &str, kind: Option<ResourceKind>, limit: Option<usize>, sort: SortOrder) -> Result<Vec<SearchHit>> {
+        let sort = if sort == SortOrder::Reputation { "reputation" } else { "relevance" };
+        let mut request = self.request(Method::GET, "/search").query(&[("q", query), ("sort", sort)]);
+        if let Some(kind) = kind {
+            request = request.query(&[("kind", kind.to_string())]);
+        }
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        self.execute_json(request, true).await
+    }
+
+    /// Lists the labels of the entries matching the selector (all labeled entries if None).
+    pub async fn list_labels(&self, selector: Option<&str>) -> Result<Vec<LabeledEntry>> {
+        let mut request = self.request(Method::GET, "/labels");
+        if let Some(selector) = selector {
+            request = request.query(&[("selector", selector)]);
+        }
+        self.execute_json(request, true).await
+    }
+
+    /// Returns the dependency graph of the registry.
+    pub async fn get_dependency_graph(&self) -> Result<DependencyGraph> {
+        self.execute_json(self.request(Method::GET, "/graph"), true).await
+    }
+
+    /// Lists the reputation of the evaluated agents, best first.
+    pub async fn list_reputations(&self) -> Result<Vec<AgentReputation>> {
+        self.execute_json(self.request(Method::GET, "/reputation"), true).await
+    }
+
+    // Administration methods
+
+    /// Subscribes a webhook to the registry changes.
+    pub async fn create_webhook(&self, request: &WebhookSubscriptionRequest) -> Result<WebhookSubscription> {
+        // Not retried: a retry after a lost response would create a second subscription
+        self.execute_json(self.request(Method::POST, "/admin/webhooks").json(request), false).await
+    }
+
+    /// Lists the webhook subscriptions.
+    pub async fn list_webhooks(&self) -> Result<Vec<WebhookSubscription>> {
+        self.execute_json(self.request(Method::GET, "/admin/webhooks"), true).await
+    }
+
+    /// Deletes a webhook subscription.
+    pub async fn delete_webhook(&self, subscription_id: &str) -> Result<String> {
+        self.execute_text(self.request_segments(Method::DELETE, &["admin", "webhooks", subscription_id])?, true).await
+    }
+
+    /// Lists the webhook deliveries that failed after every attempt.
+    pub async fn list_webhook_dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        self.execute_json(self.request(Method::GET, "/admin/webhooks/dead-letters"), true).await
+    }
+
+    /// Pushes agent scores, as aggregated by the evaluation service.
+    pub async fn push_reputations(&self, reports: &[AgentScoreReport]) -> Result<String> {
+        self.execute_text(self.request(Method::POST, "/admin/reputation").json(reports), true).await
+    }
+
+    /// Exports the whole registry as a snapshot document.
+    pub async fn export_snapshot(&self) -> Result<RegistrySnapshot> {
+        self.execute_json(self.request(Method::GET, "/admin/snapshot"), true).await
+    }
+
+    /// Imports a snapshot, either merged into or replacing the current registry.
+    pub async fn import_snapshot(&self, snapshot: &RegistrySnapshot, mode: ImportMode) -> Result<ImportSummary> {
+        let request = self.request(Method::POST, "/admin/snapshot").query(&[("mode", mode)]).json(snapshot);
+        self.execute_json(request, false).await
+    }
+
+    /// Lists the audit records of registry changes matching the query.
+    pub async fn list_audit_records(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        self.execute_json(self.request(Method::GET, "/admin/audit").query(query), true).await
     }
 }
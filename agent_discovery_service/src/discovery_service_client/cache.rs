@@ -0,0 +1,169 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
+
+use crate::discovery_service_client::agent_discovery_client::{AgentDiscoveryServiceClient, Conditional, Result};
+
+/*
+cache.rs
+client-side cache of the agent, task and tool lists of the discovery service.
+
+Lists are served from the cache while younger than the TTL. Past the TTL, they are revalidated
+with the ETag of the cached version: the discovery service answers 304 Not Modified
+without a body while the registry did not change, and the cached list is kept for another TTL.
+Registrations made through the same process invalidate the matching list right away.
+*/
+
+/// Counters of the cache lookups.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    /// Lookups served from the cache without a request.
+    pub hits: u64,
+    /// Lookups served from the cache after the discovery service confirmed it did not change.
+    pub revalidations: u64,
+    /// Lookups that downloaded the list.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Share of the lookups that did not download the list.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.revalidations + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        (self.hits + self.revalidations) as f64 / lookups as f64
+    }
+}
+
+/// A cached list along with the version it was fetched at.
+struct CachedList<T> {
+    items: Vec<T>,
+    etag: Option<String>,
+    fetched_at: Instant,
+}
+
+/// Cache of the agent, task and tool lists.
+pub struct DiscoveryCache {
+    ttl: Duration,
+    agents: Mutex<Option<CachedList<AgentDefinition>>>,
+    tasks: Mutex<Option<CachedList<TaskDefinition>>>,
+    tools: Mutex<Option<CachedList<ToolDefinition>>>,
+    hits: AtomicU64,
+    revalidations: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl DiscoveryCache {
+    pub fn new(ttl: Duration) -> Self {
+        DiscoveryCache {
+            ttl,
+            agents: Mutex::new(None),
+            tasks: Mutex::new(None),
+            tools: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            revalidations: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Lists the registered agents, from the cache when possible.
+    pub async fn agents(&self, client: &AgentDiscoveryServiceClient) -> Result<Vec<AgentDefinition>> {
+        self.lookup(&self.agents, |etag| async move {
+            client.list_agent_definitions_if_changed(etag.as_deref()).await
+        })
+        .await
+    }
+
+    /// Lists the registered tasks, from the cache when possible.
+    pub async fn tasks(&self, client: &AgentDiscoveryServiceClient) -> Result<Vec<TaskDefinition>> {
+        self.lookup(&self.tasks, |etag| async move {
+            client.list_task_definitions_if_changed(etag.as_deref()).await
+        })
+        .await
+    }
+
+    /// Lists the registered tools, from the cache when possible.
+    pub async fn tools(&self, client: &AgentDiscoveryServiceClient) -> Result<Vec<ToolDefinition>> {
+        self.lookup(&self.tools, |etag| async move {
+            client.list_tool_definitions_if_changed(etag.as_deref()).await
+        })
+        .await
+    }
+
+    pub fn invalidate_agents(&self) {
+        *self.agents.lock().unwrap() = None;
+    }
+
+    pub fn invalidate_tasks(&self) {
+        *self.tasks.lock().unwrap() = None;
+    }
+
+    pub fn invalidate_tools(&self) {
+        *self.tools.lock().unwrap() = None;
+    }
+
+    pub fn invalidate_all(&self) {
+        self.invalidate_agents();
+        self.invalidate_tasks();
+        self.invalidate_tools();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            revalidations: self.revalidations.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Serves a list from its cache slot, revalidating or fetching it with `fetch` when stale.
+    async fn lookup<T, F, Fut>(&self, slot: &Mutex<Option<CachedList<T>>>, fetch: F) -> Result<Vec<T>>
+    where
+        T: Clone,
+        F: Fn(Option<String>) -> Fut,
+        Fut: Future<Output = Result<Conditional<Vec<T>>>>,
+    {
+        let etag = match slot.lock().unwrap().as_ref() {
+            Some(cached) if cached.fetched_at.elapsed() < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(cached.items.clone());
+            }
+            Some(cached) => cached.etag.clone(),
+            None => None,
+        };
+
+        let (items, etag) = match fetch(etag).await? {
+            Conditional::Modified { value, etag } => (value, etag),
+            Conditional::NotModified => {
+                if let Some(cached) = slot.lock().unwrap().as_mut() {
+                    cached.fetched_at = Instant::now();
+                    self.revalidations.fetch_add(1, Ordering::Relaxed);
+                    return Ok(cached.items.clone());
+                }
+                // Invalidated while revalidating, download the list
+                match fetch(None).await? {
+                    Conditional::Modified { value, etag } => (value, etag),
+                    Conditional::NotModified => (Vec::new(), None),
+                }
+            }
+        };
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        *slot.lock().unwrap() = Some(CachedList {
+            items: items.clone(),
+            etag,
+            fetched_at: Instant::now(),
+        });
+        Ok(items)
+    }
+}
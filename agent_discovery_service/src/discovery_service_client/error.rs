@@ -0,0 +1,96 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use crate::discovery_server::registry::RegistryResponse;
+
+/*
+error.rs
+errors returned by the discovery service client.
+
+HTTP statuses returned by the discovery service are mapped to dedicated variants, so that
+callers can tell a missing entry or a rejected registration from a network failure.
+*/
+
+#[derive(Debug, Error)]
+pub enum DiscoveryClientError {
+    /// The service could not be reached, or the connection failed (including timeouts).
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    /// The credentials are missing or invalid (401), or do not allow the operation (403).
+    #[error("unauthorized ({status}): {message}")]
+    Unauthorized { status: u16, message: String },
+
+    /// The entry does not exist (404).
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// The request conflicts with the registry state (409), e.g. deregistering an entry
+    /// other entries depend on, or registering a near-duplicate in strict mode.
+    #[error("conflict: {message}")]
+    Conflict { message: String, warnings: Vec<String> },
+
+    /// The request was rejected as invalid (400, 422).
+    #[error("validation error: {0}")]
+    Validation(String),
+
+    /// The service failed to process the request (5xx).
+    #[error("server error ({status}): {message}")]
+    Server { status: u16, message: String },
+
+    /// Any other unsuccessful status.
+    #[error("unexpected status {status}: {message}")]
+    UnexpectedStatus { status: u16, message: String },
+
+    /// The url of the discovery service is not a valid base url.
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+
+    /// The response body could not be decoded.
+    #[error("invalid response: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+impl DiscoveryClientError {
+    /// Builds the error matching an unsuccessful response.
+    pub fn from_response(status: StatusCode, body: String) -> Self {
+        // Registry operations explain their rejections with a RegistryResponse
+        let (message, warnings) = match serde_json::from_str::<RegistryResponse>(&body) {
+            Ok(response) => (response.message, response.warnings),
+            Err(_) => (body, Vec::new()),
+        };
+
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => DiscoveryClientError::Unauthorized {
+                status: status.as_u16(),
+                message,
+            },
+            StatusCode::NOT_FOUND => DiscoveryClientError::NotFound(message),
+            StatusCode::CONFLICT => DiscoveryClientError::Conflict { message, warnings },
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => DiscoveryClientError::Validation(message),
+            status if status.is_server_error() => DiscoveryClientError::Server {
+                status: status.as_u16(),
+                message,
+            },
+            status => DiscoveryClientError::UnexpectedStatus {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+
+    /// True for the failures that may succeed when the request is sent again:
+    /// connection failures, timeouts, throttling and unavailable services.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DiscoveryClientError::Transport(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            DiscoveryClientError::Server { status, .. } => matches!(*status, 502..=504),
+            DiscoveryClientError::UnexpectedStatus { status, .. } => *status == 429,
+            _ => false,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, DiscoveryClientError::NotFound(_))
+    }
+}
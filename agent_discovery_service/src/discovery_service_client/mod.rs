@@ -1 +1,3 @@
-pub mod agent_discovery_client;
\ No newline at end of file
+pub mod agent_discovery_client;
+pub mod error;
+pub mod cache;
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::embeddings::similarity_search::tokenize;
+
+// BM25 term frequency saturation
+const BM25_K1: f32 = 1.2;
+// BM25 document length normalization
+const BM25_B: f32 = 0.75;
+
+// Statistics of an indexed document
+#[derive(Debug, Clone, Default)]
+struct IndexedDocument {
+    term_frequencies: HashMap<String, u32>,
+    length: usize,
+}
+
+// In-memory inverted index scoring documents with BM25.
+// Documents are identified by an opaque id and made of weighted fields,
+// a field of weight 2 counting each of its words twice.
+#[derive(Debug, Clone, Default)]
+pub struct Bm25Index {
+    documents: HashMap<String, IndexedDocument>,
+    // Key: term, Value: ids of the documents containing it
+    postings: HashMap<String, HashSet<String>>,
+    total_length: usize,
+}
+
+impl Bm25Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    // Indexes a document, replacing the previous version with the same id.
+    pub fn upsert(&mut self, id: &str, fields: &[(&str, u32)]) {
+        self.remove(id);
+
+        let mut document = IndexedDocument::default();
+        for (text, weight) in fields {
+            for token in tokenize(text) {
+                *document.term_frequencies.entry(token).or_default() += weight;
+                document.length += *weight as usize;
+            }
+        }
+
+        for term in document.term_frequencies.keys() {
+            self.postings.entry(term.clone()).or_default().insert(id.to_string());
+        }
+        self.total_length += document.length;
+        self.documents.insert(id.to_string(), document);
+    }
+
+    // Removes a document from the index.
+    pub fn remove(&mut self, id: &str) {
+        let Some(document) = self.documents.remove(id) else {
+            return;
+        };
+        for term in document.term_frequencies.keys() {
+            if let Some(ids) = self.postings.get_mut(term) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+        self.total_length -= document.length;
+    }
+
+    // Scores the documents containing at least one word of the query, best first.
+    pub fn search(&self, query: &str, top_k: usize) -> Vec<(String, f32)> {
+        if self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let document_count = self.documents.len() as f32;
+        let average_length = (self.total_length as f32 / document_count).max(1.0);
+
+        let mut query_terms = tokenize(query);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let mut scores: HashMap<&str, f32> = HashMap::new();
+        for term in &query_terms {
+            let Some(ids) = self.postings.get(term) else {
+                continue;
+            };
+            let document_frequency = ids.len() as f32;
+            let idf = ((document_count - document_frequency + 0.5) / (document_frequency + 0.5) + 1.0).ln();
+
+            for id in ids {
+                let document = &self.documents[id];
+                let term_frequency = document.term_frequencies[term] as f32;
+                let length_ratio = document.length as f32 / average_length;
+                let score = idf * term_frequency * (BM25_K1 + 1.0)
+                    / (term_frequency + BM25_K1 * (1.0 - BM25_B + BM25_B * length_ratio));
+                *scores.entry(id.as_str()).or_default() += score;
+            }
+        }
+
+        let mut ranked: Vec<(String, f32)> = scores.into_iter().map(|(id, score)| (id.to_string(), score)).collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(top_k);
+        ranked
+    }
+}
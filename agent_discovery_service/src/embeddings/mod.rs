@@ -1 +1,2 @@
-pub mod similarity_search;
\ No newline at end of file
+pub mod similarity_search;
+pub mod lexical_search;
@@ -23,12 +23,9 @@ pub struct VectorDB {
 }
 
 impl VectorDB {
-    // A simple cosine similarity function
-    fn cosine_similarity(a: &Embedding, b: &Embedding) -> f32 {
-        let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
-        let norm_a: f32 = a.iter().map(|x| x.powi(2)).sum::<f32>().sqrt();
-        let norm_b: f32 = b.iter().map(|x| x.powi(2)).sum::<f32>().sqrt();
-        dot_product / (norm_a * norm_b)
+    // A simple cosine similarity function. Returns 0 when one of the vectors is null.
+    pub fn cosine_similarity(a: &Embedding, b: &Embedding) -> f32 {
+        agent_similarity::cosine_similarity(a, b)
     }
 
     // Add an agent to our in-memory DB. In a real scenario, this would be an "upsert" call.
@@ -36,18 +33,33 @@ impl VectorDB {
         self.agents.push(agent);
     }
 
-    // The core search function
-    pub fn find_similar(&self, query_embedding: &Embedding, top_k: usize) -> Vec<&SearchableAgent> {
+    // Add an agent, replacing the one previously stored with the same id.
+    pub fn upsert_agent(&mut self, agent: SearchableAgent) {
+        self.remove_agent(&agent.id);
+        self.agents.push(agent);
+    }
+
+    // Remove an agent from our in-memory DB.
+    pub fn remove_agent(&mut self, id: &str) {
+        self.agents.retain(|agent| agent.id != id);
+    }
+
+    // Same as find_similar, along with the similarity of each agent
+    pub fn find_similar_scored(&self, query_embedding: &Embedding, top_k: usize) -> Vec<(f32, &SearchableAgent)> {
         let mut scored_agents: Vec<_> = self.agents.iter().map(|agent| {
             let similarity = Self::cosine_similarity(&agent.embedding, query_embedding);
             (similarity, agent)
         }).collect();
 
-        // Sort by similarity score in descending order
         scored_agents.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        scored_agents.truncate(top_k);
+        scored_agents
+    }
 
-        // Return the top_k results
-        scored_agents.into_iter().take(top_k).map(|(_, agent)| agent).collect()
+    // The core search function
+    pub fn find_similar(&self, query_embedding: &Embedding, top_k: usize) -> Vec<&SearchableAgent> {
+        // Sorted by similarity score in descending order, limited to the top_k results
+        self.find_similar_scored(query_embedding, top_k).into_iter().map(|(_, agent)| agent).collect()
     }
 }
 
@@ -57,12 +69,17 @@ pub struct DiscoveryServiceState {
     pub vector_db: Arc<Mutex<VectorDB>>,
 }
 
+// Dimension of the simulated embeddings
+pub const EMBEDDING_DIMENSION: usize = 128;
+
+// Splits a text into lowercase alphanumeric tokens, dropping stop words.
+pub use agent_similarity::tokenize;
+
 // Placeholder for a function that generates embeddings.
 // In a real implementation, this would call an external service like Vertex AI API.
 pub fn generate_embedding(text: &str) -> Embedding {
-    // For demonstration, we'll generate a deterministic, non-random vector based on the text's length.
+    // For demonstration, we hash every token of the text into a fixed size vector (feature hashing),
+    // so that texts sharing words get similar vectors.
     // THIS IS A SIMULATION. A real model would be used here.
-    let mut vec = vec![0.0; 128]; // Using a smaller dimension for the example
-    vec[0] = text.len() as f32;
-    vec
-}
\ No newline at end of file
+    agent_similarity::hashed_embedding(text, EMBEDDING_DIMENSION)
+}
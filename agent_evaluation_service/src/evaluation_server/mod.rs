@@ -1,2 +1,3 @@
 pub mod server;
-pub mod judge_agent;
\ No newline at end of file
+pub mod judge_agent;
+pub mod scores;
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use agent_models::evaluation::evaluation_models::EvaluatedAgentData;
+
+/*
+scores.rs
+aggregates the judge evaluations stored for each agent.
+
+The aggregates are consumed by the discovery service to rank agents by reputation.
+*/
+
+/// Aggregate of the judge scores of an agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentScore {
+    pub agent_id: String,
+    /// Number of evaluations carrying a score.
+    pub evaluations: usize,
+    /// Average judge score, on the 1-10 scale of the judge prompt.
+    pub average_score: f64,
+    /// Timestamp (RFC 3339) of the most recent evaluation.
+    pub last_evaluated: Option<String>,
+}
+
+/// Reads the score of a judge evaluation, whether the judge answered with a number or a string.
+fn evaluation_score(evaluated_data: &EvaluatedAgentData) -> Option<f64> {
+    let evaluation = serde_json::to_value(&evaluated_data.evaluation).ok()?;
+    match evaluation.get("score")? {
+        serde_json::Value::Number(score) => score.as_f64(),
+        serde_json::Value::String(score) => score.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Computes the score aggregate of every evaluated agent, ordered by agent id.
+pub fn aggregate_scores(evaluations: &[EvaluatedAgentData]) -> Vec<AgentScore> {
+    let mut scores: BTreeMap<&str, AgentScore> = BTreeMap::new();
+
+    for evaluated_data in evaluations {
+        let Some(score) = evaluation_score(evaluated_data) else {
+            continue;
+        };
+        let agent_id = evaluated_data.agent_log.agent_id.as_str();
+        let aggregate = scores.entry(agent_id).or_insert_with(|| AgentScore {
+            agent_id: agent_id.to_string(),
+            evaluations: 0,
+            average_score: 0.0,
+            last_evaluated: None,
+        });
+
+        // Running average
+        aggregate.evaluations += 1;
+        aggregate.average_score += (score - aggregate.average_score) / aggregate.evaluations as f64;
+        if aggregate.last_evaluated.as_deref().is_none_or(|last| last < evaluated_data.timestamp.as_str()) {
+            aggregate.last_evaluated = Some(evaluated_data.timestamp.clone());
+        }
+    }
+
+    scores.into_values().collect()
+}
@@ -10,6 +10,7 @@ use std::sync::Arc;
 use chrono::Utc;
 use agent_models::evaluation::evaluation_models::{AgentEvaluationLogData,JudgeEvaluation,EvaluatedAgentData};
 use crate::evaluation_server::judge_agent::JudgeAgent;
+use crate::evaluation_server::scores::{aggregate_scores, AgentScore};
 use configuration::AgentConfig;
 use redb::{Database, TableDefinition, ReadableTable, ReadableDatabase};
 
@@ -53,6 +54,7 @@ impl EvaluationServer {
             .route("/", get(root))
             .route("/log", post(log_evaluation))
             .route("/evaluations", get(list_evaluations))
+            .route("/scores", get(list_agent_scores))
             .with_state(app_state);
 
         Ok(Self {
@@ -141,7 +143,21 @@ async fn log_evaluation(
 async fn list_evaluations(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<EvaluatedAgentData>>, (StatusCode, String)> {
-    let db = state.db.to_owned();
+    let evaluation_list = load_evaluations(&state.db)?;
+    Ok(Json(evaluation_list))
+}
+
+/// Returns the aggregate of the judge scores of every evaluated agent.
+async fn list_agent_scores(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AgentScore>>, (StatusCode, String)> {
+    info!("Received agent scores request");
+    let evaluation_list = load_evaluations(&state.db)?;
+    Ok(Json(aggregate_scores(&evaluation_list)))
+}
+
+/// Reads every evaluation stored in the database.
+fn load_evaluations(db: &Database) -> Result<Vec<EvaluatedAgentData>, (StatusCode, String)> {
     let mut evaluation_list = Vec::new();
 
     let read_txn = match db.begin_read() {
@@ -186,5 +202,5 @@ async fn list_evaluations(
         evaluation_list.push(evaluated_data);
     }
 
-    Ok(Json(evaluation_list))
+    Ok(evaluation_list)
 }
\ No newline at end of file
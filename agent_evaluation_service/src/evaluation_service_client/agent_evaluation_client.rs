@@ -3,6 +3,8 @@ use anyhow::{Context, Result};
 use tracing::{error};
 use agent_models::evaluation::evaluation_models::{AgentEvaluationLogData,JudgeEvaluation};
 
+use crate::evaluation_server::scores::AgentScore;
+
 
 #[derive(Debug,Clone)]
 pub struct AgentEvaluationServiceClient {
@@ -39,6 +41,27 @@ impl AgentEvaluationServiceClient {
             .context("Failed to decode evaluation service JSON response")
     }
 
+    /// Returns the aggregate of the judge scores of every evaluated agent.
+    pub async fn get_agent_scores(&self) -> Result<Vec<AgentScore>> {
+        let url = format!("{}/scores", self.evaluation_service_url);
+
+        let response = self.client.get(&url)
+            .send()
+            .await
+            .context(format!("Failed to send scores request to {}", url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text_body = response.text().await
+                .context("Failed to read error response body as text")?;
+            error!("Evaluation service returned an error status: {}. Body: {}", status, text_body);
+            anyhow::bail!("Evaluation service returned an error status: {} with body: {}", status, text_body)
+        }
+
+        response.json::<Vec<AgentScore>>().await
+            .context("Failed to decode evaluation service JSON response")
+    }
+
       
     
 }
\ No newline at end of file
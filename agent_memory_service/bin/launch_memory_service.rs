@@ -11,7 +11,21 @@ use clap::Parser;
 
 
 
-use agent_memory_service::memory_server::MemoryServer;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use std::env;
+
+use agent_memory_service::context::token_estimator_from_name;
+use agent_memory_service::embedder::TextEmbedder;
+use agent_memory_service::blackboard::{BlackboardStore, InMemoryBlackboardStore, RedbBlackboardStore};
+use agent_memory_service::lifecycle::RetentionPolicy;
+use agent_memory_service::long_term::{InMemoryLongTermStore, LongTermMemoryStore, RedbLongTermStore};
+use agent_memory_service::summarizer::{ConversationSummarizer, SummaryConfig};
+use configuration::AgentConfig;
+use agent_memory_service::memory_server::{MemoryServer, MemoryServerConfig};
+use agent_memory_service::storage::{ConversationStore, InMemoryConversationStore, RedbConversationStore};
 
 /// Command-line arguments for the reimbursement server
 #[derive(Parser, Debug)]
@@ -21,6 +35,44 @@ struct Args {
     log_level: String,
     #[clap(long, default_value = "0.0.0.0:5000")]
     uri: String,
+    /// Path of the redb database persisting the conversations
+    #[clap(long, default_value = "./database/memory_db.redb")]
+    db_path: String,
+    /// Keep the conversations in memory only, they are lost on restart
+    #[clap(long)]
+    in_memory: bool,
+    /// Directory receiving the archived conversations (archiving is disabled without it)
+    #[clap(long)]
+    archive_dir: Option<String>,
+    /// Conversations inactive for longer than this many hours are archived or deleted
+    #[clap(long)]
+    retention_max_age_hours: Option<u64>,
+    /// Least recently active conversations beyond this count are archived or deleted
+    #[clap(long)]
+    retention_max_conversations: Option<usize>,
+    /// Interval between two enforcements of the retention policy
+    #[clap(long, default_value = "300")]
+    retention_interval_secs: u64,
+    /// Configuration file (TOML format) of the LLM summarizing the long conversations.
+    /// Summarization is disabled without it, the API key is read from LLM_SUMMARY_API_KEY
+    #[clap(long)]
+    summary_config_file: Option<String>,
+    /// Number of entries not covered by the summary of a conversation triggering its update
+    #[clap(long, default_value = "50")]
+    summary_threshold: usize,
+    /// Number of latest entries left out of the summaries
+    #[clap(long, default_value = "10")]
+    summary_keep_recent: usize,
+    /// Estimation of the token counts of the assembled contexts: "chars" or "words"
+    #[clap(long, default_value = "chars")]
+    token_estimator: String,
+    /// OpenAI-compatible embeddings endpoint (e.g. https://api.openai.com/v1/embeddings) of the long-term memories.
+    /// Recalls are lexical without it, the API key (if any) is read from LLM_EMBEDDING_API_KEY
+    #[clap(long)]
+    embedding_url: Option<String>,
+    /// Embedding model served by the endpoint
+    #[clap(long, default_value = "text-embedding-3-small")]
+    embedding_model: String,
 }
 
 
@@ -60,7 +112,52 @@ async fn main() -> anyhow::Result<()> {
     /************************************************/
     /* Launch Memory Server                         */
     /************************************************/ 
-    let memory_server=MemoryServer::new(args.uri).await?;
+    // Long-term memories and blackboards share the database of the conversations
+    let (store, memories, blackboard): (Arc<dyn ConversationStore>, Arc<dyn LongTermMemoryStore>, Arc<dyn BlackboardStore>) = if args.in_memory {
+        (
+            Arc::new(InMemoryConversationStore::new()),
+            Arc::new(InMemoryLongTermStore::new()),
+            Arc::new(InMemoryBlackboardStore::new()),
+        )
+    } else {
+        let redb_store = RedbConversationStore::open(&args.db_path)?;
+        let memories = RedbLongTermStore::new(redb_store.database())?;
+        let blackboard = RedbBlackboardStore::new(redb_store.database())?;
+        (Arc::new(redb_store), Arc::new(memories), Arc::new(blackboard))
+    };
+    let summarizer = match &args.summary_config_file {
+        Some(config_file) => {
+            let agent_config = AgentConfig::load_agent_config(config_file)
+                .map_err(|e| anyhow::anyhow!("Failed to load the summary configuration {}: {:?}", config_file, e))?;
+            let api_key = env::var("LLM_SUMMARY_API_KEY").expect("LLM_SUMMARY_API_KEY must be set");
+            let summary_config = SummaryConfig {
+                threshold: args.summary_threshold,
+                keep_recent: args.summary_keep_recent,
+                ..Default::default()
+            };
+            Some(Arc::new(ConversationSummarizer::from_agent_config(&agent_config, api_key, summary_config)))
+        }
+        None => None,
+    };
+
+    let embedder = args
+        .embedding_url
+        .map(|url| Arc::new(TextEmbedder::new(url, args.embedding_model, env::var("LLM_EMBEDDING_API_KEY").ok())));
+
+    let config = MemoryServerConfig {
+        archive_dir: args.archive_dir.map(PathBuf::from),
+        retention: RetentionPolicy {
+            max_age: args.retention_max_age_hours.map(|hours| Duration::from_secs(hours * 3600)),
+            max_conversations: args.retention_max_conversations,
+            interval: Duration::from_secs(args.retention_interval_secs.max(1)),
+        },
+        summarizer,
+        token_estimator: Some(token_estimator_from_name(&args.token_estimator)?),
+        memories: Some(memories),
+        embedder,
+        blackboard: Some(blackboard),
+    };
+    let memory_server=MemoryServer::with_config(args.uri, store, config).await?;
     memory_server.start_http().await?;
 
     /************************************************/
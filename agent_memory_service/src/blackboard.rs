@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use dashmap::DashMap;
+use futures::{stream, Stream, StreamExt};
+use redb::{Database, ReadableDatabase, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::memory_server::AppState;
+
+/*
+blackboard.rs
+a key/value blackboard per conversation, for the structured working state shared by the agents
+(current plan, intermediate results, flags) which does not belong in the conversation log.
+
+Values are JSON. Each key has a version, starting at 1 and incremented by every write (including
+a write over an expired value not swept yet), so that
+agents can write with compare-and-swap: a write or delete giving an `expected_version` is only
+applied if the key is still at this version (0 meaning the key must not exist), and is rejected
+with 409 Conflict otherwise.
+A write may give a TTL, after which the key expires: expired keys are no longer returned, and
+are removed by a background sweep. The redb store keeps the keys with a TTL in an index ordered
+by expiry, so that the sweep only reads the keys which expired, and only opens a write
+transaction when there are some.
+Every change (set, deleted, expired) is pushed to the watchers of the blackboard as a
+Server-Sent Event on /conversation/{id}/blackboard/events.
+*/
+
+/// Key: (conversation_id, key), Value: JSON encoded BlackboardEntry.
+const BLACKBOARD_TABLE: TableDefinition<(&str, &str), Vec<u8>> = TableDefinition::new("blackboard");
+/// Key: (expires_at in milliseconds, conversation_id, key) of the keys with a TTL.
+const BLACKBOARD_EXPIRY_TABLE: TableDefinition<(i64, &str, &str), ()> = TableDefinition::new("blackboard_expiry");
+
+/// Interval between two removals of the expired keys.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A key of a blackboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackboardEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    /// Starts at 1, incremented by every write.
+    pub version: u64,
+    pub updated_at: DateTime<Utc>,
+    /// Agent that wrote the current value.
+    pub updated_by: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl BlackboardEntry {
+    /// The entry resulting from a write over the stored one, expired or not: versions keep counting
+    /// up over an expiry, so that a compare-and-swap never mistakes the new key for the expired one.
+    fn next(key: &str, stored: Option<&BlackboardEntry>, write: &BlackboardWrite) -> anyhow::Result<Self> {
+        let now = Utc::now();
+        Ok(BlackboardEntry {
+            key: key.to_string(),
+            value: write.value.clone(),
+            version: stored.map(|entry| entry.version).unwrap_or(0) + 1,
+            updated_at: now,
+            updated_by: write.agent_id.clone(),
+            expires_at: write.expires_at(now)?,
+        })
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Expiry of the entry as ordered in the expiry index, None if it has no TTL.
+    fn expiry_millis(&self) -> Option<i64> {
+        self.expires_at.map(|expires_at| expires_at.timestamp_millis())
+    }
+}
+
+/// A write of a key, e.g. {"value": {"step": 2}, "expected_version": 1, "ttl_secs": 600, "agent_id": "planner"}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackboardWrite {
+    pub value: serde_json::Value,
+    /// Applies the write only if the key is at this version, 0 if the key must not exist.
+    pub expected_version: Option<u64>,
+    /// The key expires after this many seconds, never if None.
+    pub ttl_secs: Option<u64>,
+    pub agent_id: Option<String>,
+}
+
+impl BlackboardWrite {
+    /// Expiry of the write made at the given time, None without TTL.
+    /// Fails if the TTL is out of the range of the timestamps.
+    pub fn expires_at(&self, now: DateTime<Utc>) -> anyhow::Result<Option<DateTime<Utc>>> {
+        let Some(ttl_secs) = self.ttl_secs else {
+            return Ok(None);
+        };
+        i64::try_from(ttl_secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("ttl_secs {} is out of range", ttl_secs))
+    }
+}
+
+/// Parameters of a delete.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlackboardDelete {
+    /// Deletes the key only if it is at this version.
+    pub expected_version: Option<u64>,
+}
+
+/// Body of a 409 Conflict.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackboardConflict {
+    pub key: String,
+    pub expected_version: Option<u64>,
+    /// The current entry, None if the key does not exist.
+    pub current: Option<BlackboardEntry>,
+}
+
+/// Outcome of a write or delete.
+#[derive(Debug, Clone)]
+pub enum CasOutcome {
+    /// Applied, with the entry written or deleted.
+    Applied(BlackboardEntry),
+    /// The key to delete does not exist.
+    Missing,
+    /// The key is not at the expected version.
+    Conflict { current: Option<BlackboardEntry> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlackboardChange {
+    Set,
+    Deleted,
+    Expired,
+}
+
+/// A change of a key, pushed to the watchers of the blackboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackboardEvent {
+    pub conversation_id: String,
+    pub key: String,
+    pub change: BlackboardChange,
+    /// The entry set, or the one deleted or expired.
+    pub entry: BlackboardEntry,
+}
+
+/// Parameters of /conversation/{id}/blackboard/events
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WatchParams {
+    /// Only the changes of this key, all of them if None.
+    pub key: Option<String>,
+}
+
+/// True if the version of the current entry is the expected one, or if no version is expected.
+fn version_matches(current: Option<&BlackboardEntry>, expected_version: Option<u64>) -> bool {
+    expected_version.is_none_or(|expected_version| current.map(|entry| entry.version).unwrap_or(0) == expected_version)
+}
+
+/// Storage of the blackboards.
+/// Writes and deletes check the expected version atomically with the change.
+pub trait BlackboardStore: Send + Sync {
+    /// Returns a key, None if it does not exist or has expired.
+    fn get(&self, conversation_id: &str, key: &str) -> anyhow::Result<Option<BlackboardEntry>>;
+
+    /// Returns the keys of a blackboard which have not expired.
+    fn list(&self, conversation_id: &str) -> anyhow::Result<Vec<BlackboardEntry>>;
+
+    fn put(&self, conversation_id: &str, key: &str, write: &BlackboardWrite) -> anyhow::Result<CasOutcome>;
+
+    fn delete(&self, conversation_id: &str, key: &str, expected_version: Option<u64>) -> anyhow::Result<CasOutcome>;
+
+    /// Removes the expired keys of every blackboard, returning them with their conversation.
+    fn remove_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<(String, BlackboardEntry)>>;
+
+    /// Removes the blackboard of a conversation.
+    fn clear(&self, conversation_id: &str) -> anyhow::Result<()>;
+}
+
+/// Blackboards kept in memory.
+#[derive(Default)]
+pub struct InMemoryBlackboardStore {
+    boards: DashMap<String, HashMap<String, BlackboardEntry>>,
+}
+
+impl InMemoryBlackboardStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BlackboardStore for InMemoryBlackboardStore {
+    fn get(&self, conversation_id: &str, key: &str) -> anyhow::Result<Option<BlackboardEntry>> {
+        let now = Utc::now();
+        Ok(self
+            .boards
+            .get(conversation_id)
+            .and_then(|board| board.get(key).cloned())
+            .filter(|entry| !entry.is_expired(now)))
+    }
+
+    fn list(&self, conversation_id: &str) -> anyhow::Result<Vec<BlackboardEntry>> {
+        let now = Utc::now();
+        Ok(self
+            .boards
+            .get(conversation_id)
+            .map(|board| board.values().filter(|entry| !entry.is_expired(now)).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn put(&self, conversation_id: &str, key: &str, write: &BlackboardWrite) -> anyhow::Result<CasOutcome> {
+        // The entry guard locks the blackboard until the write is applied
+        let mut board = self.boards.entry(conversation_id.to_string()).or_default();
+        let now = Utc::now();
+        let stored = board.get(key);
+        let current = stored.filter(|entry| !entry.is_expired(now));
+        if !version_matches(current, write.expected_version) {
+            return Ok(CasOutcome::Conflict { current: current.cloned() });
+        }
+        let entry = BlackboardEntry::next(key, stored, write)?;
+        board.insert(key.to_string(), entry.clone());
+        Ok(CasOutcome::Applied(entry))
+    }
+
+    fn delete(&self, conversation_id: &str, key: &str, expected_version: Option<u64>) -> anyhow::Result<CasOutcome> {
+        let Some(mut board) = self.boards.get_mut(conversation_id) else {
+            return Ok(CasOutcome::Missing);
+        };
+        let now = Utc::now();
+        let Some(current) = board.get(key).filter(|entry| !entry.is_expired(now)) else {
+            return Ok(CasOutcome::Missing);
+        };
+        if !version_matches(Some(current), expected_version) {
+            return Ok(CasOutcome::Conflict { current: Some(current.clone()) });
+        }
+        Ok(board.remove(key).map(CasOutcome::Applied).unwrap_or(CasOutcome::Missing))
+    }
+
+    fn remove_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<(String, BlackboardEntry)>> {
+        let mut expired = Vec::new();
+        for mut board in self.boards.iter_mut() {
+            let conversation_id = board.key().clone();
+            board.retain(|_, entry| {
+                if entry.is_expired(now) {
+                    expired.push((conversation_id.clone(), entry.clone()));
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        self.boards.retain(|_, board| !board.is_empty());
+        Ok(expired)
+    }
+
+    fn clear(&self, conversation_id: &str) -> anyhow::Result<()> {
+        self.boards.remove(conversation_id);
+        Ok(())
+    }
+}
+
+/// Blackboards persisted in a redb database, usually the one of the conversations.
+pub struct RedbBlackboardStore {
+    db: Arc<Database>,
+}
+
+impl RedbBlackboardStore {
+    pub fn new(db: Arc<Database>) -> anyhow::Result<Self> {
+        let write_txn = db.begin_write()?;
+        {
+            let table = write_txn.open_table(BLACKBOARD_TABLE)?;
+            // Index the keys written before the expiry index existed
+            let mut expiry_table = write_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?;
+            for item in table.iter()? {
+                let (stored_key, value) = item?;
+                let (conversation_id, key) = stored_key.value();
+                let entry = serde_json::from_slice::<BlackboardEntry>(&value.value())?;
+                if let Some(expires_at) = entry.expiry_millis() {
+                    expiry_table.insert((expires_at, conversation_id, key), ())?;
+                }
+            }
+        }
+        write_txn.commit()?;
+        Ok(RedbBlackboardStore { db })
+    }
+
+    /// Lists the (expires_at, conversation_id, key) of the expiry index up to now, in order of expiry.
+    fn read_expiries<T>(table: &T, now: DateTime<Utc>) -> anyhow::Result<Vec<(i64, String, String)>>
+    where
+        T: ReadableTable<(i64, &'static str, &'static str), ()>,
+    {
+        let mut expiries = Vec::new();
+        for item in table.range((i64::MIN, "", "")..(now.timestamp_millis().saturating_add(1), "", ""))? {
+            let (stored_key, _) = item?;
+            let (expires_at, conversation_id, key) = stored_key.value();
+            expiries.push((expires_at, conversation_id.to_string(), key.to_string()));
+        }
+        Ok(expiries)
+    }
+
+    /// Reads a key from a table, expired or not.
+    fn read_entry<T>(table: &T, conversation_id: &str, key: &str) -> anyhow::Result<Option<BlackboardEntry>>
+    where
+        T: ReadableTable<(&'static str, &'static str), Vec<u8>>,
+    {
+        match table.get((conversation_id, key))? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the keys of a blackboard from a table, expired or not.
+    fn read_board<T>(table: &T, conversation_id: &str) -> anyhow::Result<Vec<BlackboardEntry>>
+    where
+        T: ReadableTable<(&'static str, &'static str), Vec<u8>>,
+    {
+        let mut entries = Vec::new();
+        for item in table.range((conversation_id, "")..)? {
+            let (stored_key, value) = item?;
+            if stored_key.value().0 != conversation_id {
+                break;
+            }
+            entries.push(serde_json::from_slice::<BlackboardEntry>(&value.value())?);
+        }
+        Ok(entries)
+    }
+}
+
+impl BlackboardStore for RedbBlackboardStore {
+    fn get(&self, conversation_id: &str, key: &str) -> anyhow::Result<Option<BlackboardEntry>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(BLACKBOARD_TABLE)?;
+        let now = Utc::now();
+        Ok(Self::read_entry(&table, conversation_id, key)?.filter(|entry| !entry.is_expired(now)))
+    }
+
+    fn list(&self, conversation_id: &str) -> anyhow::Result<Vec<BlackboardEntry>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(BLACKBOARD_TABLE)?;
+        let now = Utc::now();
+        let mut entries = Self::read_board(&table, conversation_id)?;
+        entries.retain(|entry| !entry.is_expired(now));
+        Ok(entries)
+    }
+
+    fn put(&self, conversation_id: &str, key: &str, write: &BlackboardWrite) -> anyhow::Result<CasOutcome> {
+        // redb serializes write transactions, the version check and the write are atomic
+        let write_txn = self.db.begin_write()?;
+        let outcome = {
+            let mut table = write_txn.open_table(BLACKBOARD_TABLE)?;
+            let mut expiry_table = write_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?;
+            let now = Utc::now();
+            let stored = Self::read_entry(&table, conversation_id, key)?;
+            let current = stored.clone().filter(|entry| !entry.is_expired(now));
+            if !version_matches(current.as_ref(), write.expected_version) {
+                return Ok(CasOutcome::Conflict { current });
+            }
+            let entry = BlackboardEntry::next(key, stored.as_ref(), write)?;
+            table.insert((conversation_id, key), serde_json::to_vec(&entry)?)?;
+            if let Some(expires_at) = stored.as_ref().and_then(BlackboardEntry::expiry_millis) {
+                expiry_table.remove((expires_at, conversation_id, key))?;
+            }
+            if let Some(expires_at) = entry.expiry_millis() {
+                expiry_table.insert((expires_at, conversation_id, key), ())?;
+            }
+            CasOutcome::Applied(entry)
+        };
+        write_txn.commit()?;
+        Ok(outcome)
+    }
+
+    fn delete(&self, conversation_id: &str, key: &str, expected_version: Option<u64>) -> anyhow::Result<CasOutcome> {
+        let write_txn = self.db.begin_write()?;
+        let outcome = {
+            let mut table = write_txn.open_table(BLACKBOARD_TABLE)?;
+            let now = Utc::now();
+            let Some(current) = Self::read_entry(&table, conversation_id, key)?.filter(|entry| !entry.is_expired(now)) else {
+                return Ok(CasOutcome::Missing);
+            };
+            if !version_matches(Some(&current), expected_version) {
+                return Ok(CasOutcome::Conflict { current: Some(current) });
+            }
+            table.remove((conversation_id, key))?;
+            if let Some(expires_at) = current.expiry_millis() {
+                write_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?.remove((expires_at, conversation_id, key))?;
+            }
+            CasOutcome::Applied(current)
+        };
+        write_txn.commit()?;
+        Ok(outcome)
+    }
+
+    fn remove_expired(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<(String, BlackboardEntry)>> {
+        // Most sweeps find nothing, they do not wait for the writers
+        {
+            let read_txn = self.db.begin_read()?;
+            let expiry_table = read_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?;
+            if Self::read_expiries(&expiry_table, now)?.is_empty() {
+                return Ok(Vec::new());
+            }
+        }
+
+        let write_txn = self.db.begin_write()?;
+        let expired = {
+            let mut table = write_txn.open_table(BLACKBOARD_TABLE)?;
+            let mut expiry_table = write_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?;
+            let mut expired = Vec::new();
+            // Read again, the keys may have changed since the read transaction
+            for (expires_at, conversation_id, key) in Self::read_expiries(&expiry_table, now)? {
+                let entry = Self::read_entry(&table, &conversation_id, &key)?;
+                // Keys expiring later within the same millisecond are left to the next sweep
+                if entry.as_ref().is_some_and(|entry| !entry.is_expired(now) && entry.expiry_millis() == Some(expires_at)) {
+                    continue;
+                }
+                expiry_table.remove((expires_at, conversation_id.as_str(), key.as_str()))?;
+                if let Some(entry) = entry.filter(|entry| entry.is_expired(now)) {
+                    table.remove((conversation_id.as_str(), key.as_str()))?;
+                    expired.push((conversation_id, entry));
+                }
+            }
+            expired
+        };
+        write_txn.commit()?;
+        Ok(expired)
+    }
+
+    fn clear(&self, conversation_id: &str) -> anyhow::Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(BLACKBOARD_TABLE)?;
+            let mut expiry_table = write_txn.open_table(BLACKBOARD_EXPIRY_TABLE)?;
+            for entry in Self::read_board(&table, conversation_id)? {
+                table.remove((conversation_id, entry.key.as_str()))?;
+                if let Some(expires_at) = entry.expiry_millis() {
+                    expiry_table.remove((expires_at, conversation_id, entry.key.as_str()))?;
+                }
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+impl AppState {
+    /// Writes a key of a blackboard and notifies its watchers.
+    pub fn write_blackboard(&self, conversation_id: &str, key: &str, write: &BlackboardWrite) -> anyhow::Result<CasOutcome> {
+        let outcome = self.blackboard.put(conversation_id, key, write)?;
+        if let CasOutcome::Applied(entry) = &outcome {
+            self.publish_blackboard_change(conversation_id, BlackboardChange::Set, entry);
+        }
+        Ok(outcome)
+    }
+
+    /// Deletes a key of a blackboard and notifies its watchers.
+    pub fn delete_blackboard_key(&self, conversation_id: &str, key: &str, expected_version: Option<u64>) -> anyhow::Result<CasOutcome> {
+        let outcome = self.blackboard.delete(conversation_id, key, expected_version)?;
+        if let CasOutcome::Applied(entry) = &outcome {
+            self.publish_blackboard_change(conversation_id, BlackboardChange::Deleted, entry);
+        }
+        Ok(outcome)
+    }
+
+    fn publish_blackboard_change(&self, conversation_id: &str, change: BlackboardChange, entry: &BlackboardEntry) {
+        self.blackboard_events.publish(
+            conversation_id,
+            &BlackboardEvent {
+                conversation_id: conversation_id.to_string(),
+                key: entry.key.clone(),
+                change,
+                entry: entry.clone(),
+            },
+        );
+    }
+
+    /// Removes the expired keys of the blackboards in the background, notifying their watchers.
+    pub fn spawn_blackboard_sweeper(&self) -> tokio::task::JoinHandle<()> {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                // The stores block on their database, keep the sweep off the async workers
+                let blackboard = state.blackboard.clone();
+                match tokio::task::spawn_blocking(move || blackboard.remove_expired(Utc::now())).await {
+                    Ok(Ok(expired)) => {
+                        for (conversation_id, entry) in expired {
+                            state.publish_blackboard_change(&conversation_id, BlackboardChange::Expired, &entry);
+                        }
+                    }
+                    Ok(Err(e)) => warn!("Failed to remove the expired blackboard keys: {}", e),
+                    Err(e) => warn!("The removal of the expired blackboard keys panicked: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// Converts the outcome of a write or delete into a response, 409 with the current entry on a conflict.
+fn cas_response(key: &str, expected_version: Option<u64>, outcome: CasOutcome) -> Response {
+    match outcome {
+        CasOutcome::Applied(entry) => Json(entry).into_response(),
+        CasOutcome::Missing => (StatusCode::NOT_FOUND, format!("Key '{}' not found", key)).into_response(),
+        CasOutcome::Conflict { current } => (
+            StatusCode::CONFLICT,
+            Json(BlackboardConflict {
+                key: key.to_string(),
+                expected_version,
+                current,
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Returns the keys of the blackboard of a conversation.
+pub async fn get_blackboard(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<Vec<BlackboardEntry>>, (StatusCode, String)> {
+    info!("Received get_blackboard request for id: {}", conversation_id);
+
+    let mut entries = state
+        .blackboard
+        .list(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read blackboard: {}", e)))?;
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(Json(entries))
+}
+
+pub async fn get_blackboard_key(
+    State(state): State<AppState>,
+    Path((conversation_id, key)): Path<(String, String)>,
+) -> Result<Json<BlackboardEntry>, (StatusCode, String)> {
+    info!("Received get_blackboard_key request for id: {}, key: {}", conversation_id, key);
+
+    state
+        .blackboard
+        .get(&conversation_id, &key)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read blackboard: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Key '{}' not found", key)))
+}
+
+/// Writes a key, with compare-and-swap if an expected version is given.
+pub async fn put_blackboard_key(
+    State(state): State<AppState>,
+    Path((conversation_id, key)): Path<(String, String)>,
+    Json(write): Json<BlackboardWrite>,
+) -> Result<Response, (StatusCode, String)> {
+    info!("Received put_blackboard_key request for id: {}, key: {}", conversation_id, key);
+
+    if let Err(e) = write.expires_at(Utc::now()) {
+        return Err((StatusCode::BAD_REQUEST, e.to_string()));
+    }
+
+    let outcome = state
+        .write_blackboard(&conversation_id, &key, &write)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write blackboard: {}", e)))?;
+    Ok(cas_response(&key, write.expected_version, outcome))
+}
+
+/// Deletes a key, e.g. /conversation/{id}/blackboard/keys/plan?expected_version=3
+pub async fn delete_blackboard_key(
+    State(state): State<AppState>,
+    Path((conversation_id, key)): Path<(String, String)>,
+    Query(params): Query<BlackboardDelete>,
+) -> Result<Response, (StatusCode, String)> {
+    info!("Received delete_blackboard_key request for id: {}, key: {}", conversation_id, key);
+
+    let outcome = state
+        .delete_blackboard_key(&conversation_id, &key, params.expected_version)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write blackboard: {}", e)))?;
+    Ok(cas_response(&key, params.expected_version, outcome))
+}
+
+/// Streams the changes of the blackboard of a conversation as Server-Sent Events,
+/// e.g. /conversation/{id}/blackboard/events?key=plan
+pub async fn watch_blackboard(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+    Query(params): Query<WatchParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("Received watch_blackboard request for id: {}", conversation_id);
+
+    let receiver = state.blackboard_events.subscribe(&conversation_id);
+    let changes = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                // Changes are not replayed, the watcher reads the blackboard again to catch up
+                Err(RecvError::Lagged(skipped)) => warn!("Blackboard watcher lagged by {} changes", skipped),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .filter(move |event| std::future::ready(params.key.as_ref().is_none_or(|key| &event.key == key)));
+
+    let events = changes.map(|event| {
+        Ok(match serde_json::to_string(&event) {
+            Ok(data) => Event::default().event("change").data(data),
+            Err(e) => Event::default().event("error").data(e.to_string()),
+        })
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use agent_models::memory::memory_models::Role;
+
+use crate::memory_server::AppState;
+use crate::models::LogRecord;
+
+/*
+context.rs
+assembles a prompt-ready context of a conversation within a token budget.
+
+The context is made, in this order of priority, of:
+1. the pinned entries: the system entries, which set the rules of the conversation
+2. the rolling summary of the older entries, if the conversation has one
+3. as many of the most recent entries as fit in the remaining budget
+
+Token counts are estimated by a pluggable TokenEstimator, a character ratio by default,
+since the memory service does not know the tokenizer of the model reading the context.
+The context is returned either as plain text or as OpenAI chat messages.
+*/
+
+/// Tokens added by the chat format around each message.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+const DEFAULT_MAX_TOKENS: usize = 4000;
+
+/// Estimates the number of tokens of a text.
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, text: &str) -> usize;
+}
+
+/// Estimates tokens from the number of characters, about 4 per token for English text.
+#[derive(Debug, Clone)]
+pub struct CharRatioEstimator {
+    pub chars_per_token: f32,
+}
+
+impl Default for CharRatioEstimator {
+    fn default() -> Self {
+        CharRatioEstimator { chars_per_token: 4.0 }
+    }
+}
+
+impl TokenEstimator for CharRatioEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        (text.chars().count() as f32 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// Estimates tokens from the number of words, about 0.75 word per token for English text.
+#[derive(Debug, Clone)]
+pub struct WordRatioEstimator {
+    pub tokens_per_word: f32,
+}
+
+impl Default for WordRatioEstimator {
+    fn default() -> Self {
+        WordRatioEstimator { tokens_per_word: 1.33 }
+    }
+}
+
+impl TokenEstimator for WordRatioEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        (text.split_whitespace().count() as f32 * self.tokens_per_word).ceil() as usize
+    }
+}
+
+/// Format of the assembled context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextFormat {
+    /// A single prompt text.
+    #[default]
+    Text,
+    /// OpenAI chat messages.
+    Messages,
+}
+
+/// Parameters of /conversation/{id}/context
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContextParams {
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub format: ContextFormat,
+}
+
+/// A message in the OpenAI chat format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// "system", "user" or "assistant".
+    pub role: String,
+    pub content: String,
+    /// Agent that wrote the message, restricted to the characters allowed by the chat format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Context of a conversation fitting in a token budget.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssembledContext {
+    pub conversation_id: String,
+    pub format: ContextFormat,
+    pub max_tokens: usize,
+    pub estimated_tokens: usize,
+    /// True if the rolling summary is part of the context.
+    pub summary_included: bool,
+    /// Seq of the entries included in the context.
+    pub included_seqs: Vec<u64>,
+    /// Number of entries left out of the context (summarized ones aside).
+    pub omitted_entries: usize,
+    /// The context, with ContextFormat::Text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// The context, with ContextFormat::Messages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<ChatMessage>>,
+}
+
+/// A piece of the context along with its estimated size.
+struct ContextPart {
+    seq: Option<u64>,
+    message: ChatMessage,
+    text: String,
+    tokens: usize,
+}
+
+impl ContextPart {
+    fn new(seq: Option<u64>, message: ChatMessage, text: String, format: ContextFormat, estimator: &dyn TokenEstimator) -> Self {
+        let tokens = match format {
+            ContextFormat::Text => estimator.estimate(&text) + 1,
+            ContextFormat::Messages => estimator.estimate(&message.content) + MESSAGE_OVERHEAD_TOKENS,
+        };
+        ContextPart { seq, message, text, tokens }
+    }
+
+    fn from_record(record: &LogRecord, format: ContextFormat, estimator: &dyn TokenEstimator) -> Self {
+        let role = match record.entry.role {
+            Role::System => "system",
+            Role::User => "user",
+            _ => "assistant",
+        };
+        let speaker = record
+            .entry
+            .agent_id
+            .clone()
+            .unwrap_or_else(|| format!("{:?}", record.entry.role));
+        let name = record.entry.agent_id.as_ref().map(|agent_id| {
+            agent_id
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .take(64)
+                .collect()
+        });
+
+        let message = ChatMessage {
+            role: role.to_string(),
+            content: record.entry.content.clone(),
+            name,
+        };
+        let text = format!("[{}] {}", speaker, record.entry.content);
+        ContextPart::new(Some(record.seq), message, text, format, estimator)
+    }
+}
+
+impl AppState {
+    /// Assembles the context of a conversation, None if the conversation does not exist.
+    pub fn assemble_context(
+        &self,
+        conversation_id: &str,
+        max_tokens: usize,
+        format: ContextFormat,
+    ) -> anyhow::Result<Option<AssembledContext>> {
+        let Some(records) = self.store.get(conversation_id)? else {
+            return Ok(None);
+        };
+        let summary = self.store.get_summary(conversation_id)?;
+        let covered_until_seq = summary.as_ref().map(|summary| summary.covered_until_seq).unwrap_or(0);
+        let estimator = self.token_estimator.as_ref();
+
+        let mut budget = max_tokens;
+        let mut fits = |part: &ContextPart| {
+            if part.tokens <= budget {
+                budget -= part.tokens;
+                true
+            } else {
+                false
+            }
+        };
+
+        // 1. Pinned system entries, oldest first
+        let pinned: Vec<ContextPart> = records
+            .iter()
+            .filter(|record| matches!(record.entry.role, Role::System))
+            .map(|record| ContextPart::from_record(record, format, estimator))
+            .filter(|part| fits(part))
+            .collect();
+
+        // 2. Summary of the older entries
+        let summary_part = summary
+            .map(|summary| {
+                let content = format!("Summary of the earlier conversation:\n{}", summary.summary);
+                let message = ChatMessage {
+                    role: "system".to_string(),
+                    content: content.clone(),
+                    name: None,
+                };
+                ContextPart::new(None, message, content, format, estimator)
+            })
+            .filter(|part| fits(part));
+
+        // 3. Most recent entries not covered by the summary, as many as fit
+        let candidates: Vec<&LogRecord> = records
+            .iter()
+            .filter(|record| !matches!(record.entry.role, Role::System))
+            .filter(|record| summary_part.is_none() || record.seq > covered_until_seq)
+            .collect();
+        let mut recent: Vec<ContextPart> = Vec::new();
+        for record in candidates.iter().rev() {
+            let part = ContextPart::from_record(record, format, estimator);
+            if !fits(&part) {
+                break;
+            }
+            recent.push(part);
+        }
+        recent.reverse();
+        let omitted_entries = candidates.len() - recent.len();
+
+        let summary_included = summary_part.is_some();
+        let parts: Vec<ContextPart> = pinned.into_iter().chain(summary_part).chain(recent).collect();
+        let estimated_tokens = parts.iter().map(|part| part.tokens).sum();
+        let included_seqs = parts.iter().filter_map(|part| part.seq).collect();
+
+        let (text, messages) = match format {
+            ContextFormat::Text => (
+                Some(parts.iter().map(|part| part.text.as_str()).collect::<Vec<&str>>().join("\n")),
+                None,
+            ),
+            ContextFormat::Messages => (None, Some(parts.into_iter().map(|part| part.message).collect())),
+        };
+
+        Ok(Some(AssembledContext {
+            conversation_id: conversation_id.to_string(),
+            format,
+            max_tokens,
+            estimated_tokens,
+            summary_included,
+            included_seqs,
+            omitted_entries,
+            text,
+            messages,
+        }))
+    }
+}
+
+/// Returns a prompt-ready context of a conversation,
+/// e.g. /conversation/{id}/context?max_tokens=2000&format=messages
+pub async fn get_conversation_context(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+    Query(params): Query<ContextParams>,
+) -> Result<Json<AssembledContext>, (StatusCode, String)> {
+    info!("Received get_conversation_context request for id: {}", conversation_id);
+
+    let max_tokens = params.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    state
+        .assemble_context(&conversation_id, max_tokens, params.format)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to assemble context: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)))
+}
+
+/// Parses the name of a token estimator: "chars" (default) or "words".
+pub fn token_estimator_from_name(name: &str) -> anyhow::Result<Arc<dyn TokenEstimator>> {
+    match name.to_lowercase().as_str() {
+        "chars" => Ok(Arc::new(CharRatioEstimator::default())),
+        "words" => Ok(Arc::new(WordRatioEstimator::default())),
+        other => Err(anyhow::anyhow!("Unknown token estimator: {}", other)),
+    }
+}
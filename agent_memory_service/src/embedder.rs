@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+/*
+embedder.rs
+computes the embeddings of the long-term memories and of the recall queries with an embedding model,
+so that recalls match memories by meaning rather than by shared words.
+
+The model is any OpenAI-compatible /embeddings endpoint (OpenAI, Mistral, Ollama, vLLM, ...).
+Agents storing memories with their own embeddings must use the same model.
+*/
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+/// Computes embeddings with an OpenAI-compatible endpoint.
+pub struct TextEmbedder {
+    client: reqwest::Client,
+    url: String,
+    model_id: String,
+    api_key: Option<String>,
+}
+
+impl TextEmbedder {
+    /// The url is the full url of the endpoint, e.g. https://api.openai.com/v1/embeddings
+    pub fn new(url: String, model_id: String, api_key: Option<String>) -> Self {
+        TextEmbedder {
+            client: reqwest::Client::new(),
+            url,
+            model_id,
+            api_key,
+        }
+    }
+
+    /// Returns the embedding of a text.
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut request = self.client.post(&self.url).json(&EmbeddingRequest {
+            model: &self.model_id,
+            input: text,
+        });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response: EmbeddingResponse = request.send().await?.error_for_status()?.json().await?;
+        let embedding = response
+            .data
+            .into_iter()
+            .next()
+            .map(|data| data.embedding)
+            .ok_or_else(|| anyhow::anyhow!("The embedding response is empty"))?;
+
+        trace!("Embedded a text of {} characters into {} dimensions", text.len(), embedding.len());
+        Ok(embedding)
+    }
+}
@@ -1,3 +1,12 @@
 pub mod memory_server;
 pub mod memory_service_client;
 pub mod models;
+pub mod storage;
+pub mod lifecycle;
+pub mod summarizer;
+pub mod context;
+pub mod long_term;
+pub mod search;
+pub mod stream;
+pub mod blackboard;
+pub mod embedder;
@@ -0,0 +1,273 @@
+use std::path::{Path as FsPath, PathBuf};
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::memory_server::AppState;
+use crate::models::{ConversationMetadata, LogRecord};
+
+/*
+lifecycle.rs
+lists, deletes and archives conversations, and enforces the retention policy.
+
+Archiving a conversation writes it as a JSON document to the archive directory (cold storage),
+then deletes the archived records from the store. Nothing is deleted if the archive cannot be
+written, and the entries appended meanwhile are kept in the store.
+The retention task runs at a fixed interval: conversations inactive for longer than the max age
+are removed first, then the least recently active ones until at most max conversations are
+left. Removed conversations are archived when an archive directory is configured, and deleted
+otherwise.
+*/
+
+/// Limits on the conversations kept by the memory service. No limit by default.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Conversations inactive for longer are removed.
+    pub max_age: Option<Duration>,
+    /// Least recently active conversations are removed beyond this count.
+    pub max_conversations: Option<usize>,
+    /// Interval between two enforcements of the policy.
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_age: None,
+            max_conversations: None,
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_conversations.is_some()
+    }
+}
+
+/// A conversation as written to the archive.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchivedConversation {
+    pub metadata: ConversationMetadata,
+    pub records: Vec<LogRecord>,
+    pub archived_at: chrono::DateTime<Utc>,
+}
+
+/// Writes conversations to the archive directory.
+#[derive(Debug, Clone)]
+pub struct ConversationArchiver {
+    archive_dir: PathBuf,
+}
+
+impl ConversationArchiver {
+    pub fn new(archive_dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let archive_dir = archive_dir.into();
+        std::fs::create_dir_all(&archive_dir)?;
+        Ok(ConversationArchiver { archive_dir })
+    }
+
+    pub fn archive_dir(&self) -> &FsPath {
+        &self.archive_dir
+    }
+
+    /// Writes a conversation to the archive, returning the path of the archive file.
+    pub fn write(&self, conversation: &ArchivedConversation) -> anyhow::Result<PathBuf> {
+        // Conversation ids are chosen by the clients, keep them from escaping the archive directory
+        let file_stem: String = conversation
+            .metadata
+            .conversation_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let file_name = format!("{}-{}.json", file_stem, conversation.archived_at.format("%Y%m%dT%H%M%S%.3fZ"));
+        let path = self.archive_dir.join(file_name);
+        std::fs::write(&path, serde_json::to_vec_pretty(conversation)?)?;
+        Ok(path)
+    }
+}
+
+/// Outcome of the removal of a conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemovedConversation {
+    pub conversation_id: String,
+    /// Path of the archive file, None if the conversation was deleted.
+    pub archive_path: Option<String>,
+}
+
+impl AppState {
+    /// Archives a conversation and removes the archived records from the store.
+    /// Returns None if the conversation does not exist.
+    pub fn archive_conversation(&self, archiver: &ConversationArchiver, conversation_id: &str) -> anyhow::Result<Option<PathBuf>> {
+        let records = self.store.get(conversation_id)?.unwrap_or_default();
+        let Some(metadata) = ConversationMetadata::from_records(conversation_id, &records) else {
+            return Ok(None);
+        };
+        let last_seq = metadata.last_seq;
+        let path = archiver.write(&ArchivedConversation {
+            metadata,
+            records,
+            archived_at: Utc::now(),
+        })?;
+
+        // Only the archived records are deleted, the ones appended meanwhile stay in the store
+        let remaining = self.store.delete_until(conversation_id, last_seq)?;
+        if remaining.is_empty() {
+            self.forget_conversation(conversation_id)?;
+        } else {
+            self.search_index.remove_conversation(conversation_id);
+            for record in &remaining {
+                self.search_index.index(conversation_id, record);
+            }
+        }
+        Ok(Some(path))
+    }
+
+    /// Deletes a conversation from the store and the search index, along with its blackboard.
+    /// Returns false if the conversation did not exist.
+    pub fn remove_conversation(&self, conversation_id: &str) -> anyhow::Result<bool> {
+        let existed = self.store.delete(conversation_id)?;
+        self.forget_conversation(conversation_id)?;
+        Ok(existed)
+    }
+
+    /// Drops what is kept about a conversation besides the store: blackboard, search index and streams.
+    fn forget_conversation(&self, conversation_id: &str) -> anyhow::Result<()> {
+        self.blackboard.clear(conversation_id)?;
+        self.search_index.remove_conversation(conversation_id);
+        self.broadcaster.close(conversation_id);
+        self.blackboard_events.close(conversation_id);
+        Ok(())
+    }
+
+    /// Removes a conversation, archiving it if an archive directory is configured.
+    fn retire_conversation(&self, conversation_id: &str) -> anyhow::Result<RemovedConversation> {
+        let archive_path = match &self.archiver {
+            Some(archiver) => self
+                .archive_conversation(archiver, conversation_id)?
+                .map(|path| path.display().to_string()),
+            None => {
+                self.remove_conversation(conversation_id)?;
+                None
+            }
+        };
+        Ok(RemovedConversation {
+            conversation_id: conversation_id.to_string(),
+            archive_path,
+        })
+    }
+
+    /// Removes the conversations beyond the retention policy.
+    pub fn enforce_retention(&self, policy: &RetentionPolicy) -> anyhow::Result<Vec<RemovedConversation>> {
+        let mut conversations = self.store.list_conversations()?;
+        // Most recently active first
+        conversations.sort_by(|a, b| b.last_activity.cmp(&a.last_activity));
+
+        let cutoff = match policy.max_age {
+            Some(max_age) => Some(Utc::now() - chrono::Duration::from_std(max_age)?),
+            None => None,
+        };
+        let max_conversations = policy.max_conversations.unwrap_or(usize::MAX);
+
+        let mut removed = Vec::new();
+        for (rank, metadata) in conversations.iter().enumerate() {
+            let expired = cutoff.is_some_and(|cutoff| metadata.last_activity < cutoff);
+            if expired || rank >= max_conversations {
+                removed.push(self.retire_conversation(&metadata.conversation_id)?);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Enforces the retention policy in the background.
+    pub fn spawn_retention(&self, policy: RetentionPolicy) -> tokio::task::JoinHandle<()> {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(policy.interval);
+            loop {
+                interval.tick().await;
+                // The store and the archive block on disk, keep the enforcement off the async workers
+                let (enforcer, enforced_policy) = (state.clone(), policy.clone());
+                match tokio::task::spawn_blocking(move || enforcer.enforce_retention(&enforced_policy)).await {
+                    Ok(Ok(removed)) if !removed.is_empty() => info!("Retention policy removed {} conversations", removed.len()),
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("Failed to enforce the retention policy: {}", e),
+                    Err(e) => warn!("The enforcement of the retention policy panicked: {}", e),
+                }
+            }
+        })
+    }
+}
+
+/// Lists the stored conversations, most recently active first.
+pub async fn list_conversations(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ConversationMetadata>>, (StatusCode, String)> {
+    info!("Received list_conversations request");
+
+    let mut conversations = state
+        .store
+        .list_conversations()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list conversations: {}", e)))?;
+    conversations.sort_by(|a, b| b.last_activity.cmp(&a.last_activity));
+    Ok(Json(conversations))
+}
+
+/// Returns the metadata of a conversation.
+pub async fn get_conversation_metadata(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<ConversationMetadata>, (StatusCode, String)> {
+    info!("Received get_conversation_metadata request for id: {}", conversation_id);
+
+    state
+        .store
+        .metadata(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)))
+}
+
+/// Deletes a conversation.
+pub async fn delete_conversation(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    info!("Received delete_conversation request for id: {}", conversation_id);
+
+    let deleted = state
+        .remove_conversation(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete conversation: {}", e)))?;
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)));
+    }
+    Ok((StatusCode::OK, format!("Conversation '{}' deleted", conversation_id)))
+}
+
+/// Archives a conversation to cold storage and removes it from the store.
+pub async fn archive_conversation(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<RemovedConversation>, (StatusCode, String)> {
+    info!("Received archive_conversation request for id: {}", conversation_id);
+
+    let archiver = state.archiver.clone().ok_or_else(|| {
+        (StatusCode::NOT_IMPLEMENTED, "No archive directory is configured".to_string())
+    })?;
+    let path = state
+        .archive_conversation(&archiver, &conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to archive conversation: {}", e)))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)))?;
+
+    Ok(Json(RemovedConversation {
+        conversation_id,
+        archive_path: Some(path.display().to_string()),
+    }))
+}
@@ -0,0 +1,375 @@
+use std::sync::Arc;
+
+use agent_similarity::{cosine_similarity, hashed_embedding};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use redb::{Database, ReadableDatabase, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::memory_server::AppState;
+
+/*
+long_term.rs
+long-term memory of the agents: facts and notes kept beyond a conversation, recalled by similarity.
+
+Memories belong to a namespace ("default" if none is given) and may be attached to an agent
+and/or a conversation; recalls are scoped by any of the three.
+
+Recalls are semantic when an embedding model is configured: the service embeds the memories as
+they are stored and the queries as they are recalled, and ranks the memories by cosine similarity.
+Agents may also store and recall with their own embeddings, computed by the same model. Only the
+memories with an embedding of the same dimension as the query are recalled this way.
+Without embedding model nor query embedding, recalls fall back to lexical matching: the texts are
+compared with hashed bag-of-words embeddings, which only capture shared words ("preferred
+airline" does not find "the user flies Lufthansa"). A recall uses one of the two scores for
+every memory, as they are not comparable.
+*/
+
+/// Key: memory_id, Value: JSON encoded Memory.
+const MEMORIES_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("memories");
+
+/// Dimension of the hashed bag-of-words embeddings.
+const HASHED_EMBEDDING_DIMENSION: usize = 256;
+
+const DEFAULT_NAMESPACE: &str = "default";
+const DEFAULT_TOP_K: usize = 5;
+
+/// A memory to store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryRequest {
+    pub content: String,
+    pub namespace: Option<String>,
+    pub agent_id: Option<String>,
+    pub conversation_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Embedding of the content, computed by the agent.
+    pub embedding: Option<Vec<f32>>,
+    /// Free-form data attached to the memory.
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A stored memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Memory {
+    pub memory_id: Uuid,
+    pub content: String,
+    pub namespace: String,
+    pub agent_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<MemoryRequest> for Memory {
+    fn from(request: MemoryRequest) -> Self {
+        Memory {
+            memory_id: Uuid::new_v4(),
+            content: request.content,
+            namespace: request.namespace.unwrap_or_else(|| DEFAULT_NAMESPACE.to_string()),
+            agent_id: request.agent_id,
+            conversation_id: request.conversation_id,
+            tags: request.tags,
+            embedding: request.embedding,
+            metadata: request.metadata,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Scope of a listing or a recall. Unset fields do not restrict it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryScope {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    /// Only the memories carrying this tag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+impl MemoryScope {
+    pub fn contains(&self, memory: &Memory) -> bool {
+        self.namespace.as_ref().is_none_or(|namespace| &memory.namespace == namespace)
+            && self.agent_id.as_ref().is_none_or(|agent_id| memory.agent_id.as_ref() == Some(agent_id))
+            && self
+                .conversation_id
+                .as_ref()
+                .is_none_or(|conversation_id| memory.conversation_id.as_ref() == Some(conversation_id))
+            && self.tag.as_ref().is_none_or(|tag| memory.tags.contains(tag))
+    }
+}
+
+/// A natural-language recall, e.g. {"query": "preferred airline of the user", "agent_id": "travel_agent", "top_k": 3}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecallRequest {
+    pub query: Option<String>,
+    /// Embedding of the query, computed with the model used for the stored embeddings.
+    pub embedding: Option<Vec<f32>>,
+    pub top_k: Option<usize>,
+    /// Memories scoring below are not returned.
+    pub min_score: Option<f32>,
+    #[serde(flatten)]
+    pub scope: MemoryScope,
+}
+
+/// A recalled memory and its similarity with the query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecalledMemory {
+    pub score: f32,
+    pub memory: Memory,
+}
+
+/// Storage of the long-term memories.
+pub trait LongTermMemoryStore: Send + Sync {
+    fn put(&self, memory: Memory) -> anyhow::Result<()>;
+
+    fn get(&self, memory_id: &Uuid) -> anyhow::Result<Option<Memory>>;
+
+    /// Deletes a memory. Returns false if the memory did not exist.
+    fn delete(&self, memory_id: &Uuid) -> anyhow::Result<bool>;
+
+    /// Lists the memories within the scope.
+    fn list(&self, scope: &MemoryScope) -> anyhow::Result<Vec<Memory>>;
+}
+
+/// Memories kept in memory.
+#[derive(Default)]
+pub struct InMemoryLongTermStore {
+    memories: DashMap<Uuid, Memory>,
+}
+
+impl InMemoryLongTermStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LongTermMemoryStore for InMemoryLongTermStore {
+    fn put(&self, memory: Memory) -> anyhow::Result<()> {
+        self.memories.insert(memory.memory_id, memory);
+        Ok(())
+    }
+
+    fn get(&self, memory_id: &Uuid) -> anyhow::Result<Option<Memory>> {
+        Ok(self.memories.get(memory_id).map(|entry| entry.clone()))
+    }
+
+    fn delete(&self, memory_id: &Uuid) -> anyhow::Result<bool> {
+        Ok(self.memories.remove(memory_id).is_some())
+    }
+
+    fn list(&self, scope: &MemoryScope) -> anyhow::Result<Vec<Memory>> {
+        Ok(self
+            .memories
+            .iter()
+            .filter(|entry| scope.contains(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect())
+    }
+}
+
+/// Memories persisted in a redb database, usually the one of the conversations.
+pub struct RedbLongTermStore {
+    db: Arc<Database>,
+}
+
+impl RedbLongTermStore {
+    pub fn new(db: Arc<Database>) -> anyhow::Result<Self> {
+        let write_txn = db.begin_write()?;
+        {
+            let _ = write_txn.open_table(MEMORIES_TABLE)?;
+        }
+        write_txn.commit()?;
+        Ok(RedbLongTermStore { db })
+    }
+}
+
+impl LongTermMemoryStore for RedbLongTermStore {
+    fn put(&self, memory: Memory) -> anyhow::Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(MEMORIES_TABLE)?;
+            table.insert(memory.memory_id.to_string().as_str(), serde_json::to_vec(&memory)?)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    fn get(&self, memory_id: &Uuid) -> anyhow::Result<Option<Memory>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(MEMORIES_TABLE)?;
+        match table.get(memory_id.to_string().as_str())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, memory_id: &Uuid) -> anyhow::Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        let existed = {
+            let mut table = write_txn.open_table(MEMORIES_TABLE)?;
+            table.remove(memory_id.to_string().as_str())?.is_some()
+        };
+        write_txn.commit()?;
+        Ok(existed)
+    }
+
+    fn list(&self, scope: &MemoryScope) -> anyhow::Result<Vec<Memory>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(MEMORIES_TABLE)?;
+        let mut memories = Vec::new();
+        for item in table.iter()? {
+            let (_, value) = item?;
+            let memory: Memory = serde_json::from_slice(&value.value())?;
+            if scope.contains(&memory) {
+                memories.push(memory);
+            }
+        }
+        Ok(memories)
+    }
+}
+
+impl AppState {
+    /// Recalls the memories of the scope most similar to the query, best first.
+    pub fn recall_memories(&self, request: &RecallRequest) -> anyhow::Result<Vec<RecalledMemory>> {
+        // The embeddings of the agent if provided, the words of the texts otherwise
+        let query_text_embedding = match (&request.embedding, &request.query) {
+            (Some(_), _) => None,
+            (None, Some(query)) => Some(hashed_embedding(query, HASHED_EMBEDDING_DIMENSION)),
+            (None, None) => return Err(anyhow::anyhow!("A recall needs a query or an embedding")),
+        };
+
+        let mut recalled: Vec<RecalledMemory> = self
+            .memories
+            .list(&request.scope)?
+            .into_iter()
+            .filter_map(|memory| {
+                let score = match (&request.embedding, &query_text_embedding) {
+                    (Some(query), _) => match &memory.embedding {
+                        Some(stored) if stored.len() == query.len() => cosine_similarity(query, stored),
+                        _ => return None,
+                    },
+                    (None, Some(query)) => cosine_similarity(query, &hashed_embedding(&memory.content, HASHED_EMBEDDING_DIMENSION)),
+                    (None, None) => return None,
+                };
+                Some(RecalledMemory { score, memory })
+            })
+            .filter(|recalled| request.min_score.is_none_or(|min_score| recalled.score >= min_score))
+            .collect();
+
+        recalled.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        recalled.truncate(request.top_k.unwrap_or(DEFAULT_TOP_K));
+        Ok(recalled)
+    }
+}
+
+/// Stores a long-term memory.
+pub async fn store_memory(
+    State(state): State<AppState>,
+    Json(request): Json<MemoryRequest>,
+) -> Result<(StatusCode, Json<Memory>), (StatusCode, String)> {
+    info!("Received store_memory request in namespace: {:?}", request.namespace);
+
+    if request.content.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A memory needs a content".to_string()));
+    }
+    let mut memory = Memory::from(request);
+    if let (None, Some(embedder)) = (&memory.embedding, &state.embedder) {
+        let embedding = embedder
+            .embed(&memory.content)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to embed memory: {}", e)))?;
+        memory.embedding = Some(embedding);
+    }
+    state
+        .memories
+        .put(memory.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store memory: {}", e)))?;
+    Ok((StatusCode::CREATED, Json(memory)))
+}
+
+/// Lists the memories of a scope, e.g. /memories?namespace=travel&agent_id=planner
+pub async fn list_memories(
+    State(state): State<AppState>,
+    Query(scope): Query<MemoryScope>,
+) -> Result<Json<Vec<Memory>>, (StatusCode, String)> {
+    info!("Received list_memories request for scope: {:?}", scope);
+
+    let mut memories = state
+        .memories
+        .list(&scope)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list memories: {}", e)))?;
+    memories.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(Json(memories))
+}
+
+pub async fn get_memory(
+    State(state): State<AppState>,
+    Path(memory_id): Path<Uuid>,
+) -> Result<Json<Memory>, (StatusCode, String)> {
+    info!("Received get_memory request for id: {}", memory_id);
+
+    state
+        .memories
+        .get(&memory_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read memory: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Memory '{}' not found", memory_id)))
+}
+
+pub async fn delete_memory(
+    State(state): State<AppState>,
+    Path(memory_id): Path<Uuid>,
+) -> Result<(StatusCode, String), (StatusCode, String)> {
+    info!("Received delete_memory request for id: {}", memory_id);
+
+    let deleted = state
+        .memories
+        .delete(&memory_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete memory: {}", e)))?;
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, format!("Memory '{}' not found", memory_id)));
+    }
+    Ok((StatusCode::OK, format!("Memory '{}' deleted", memory_id)))
+}
+
+/// Recalls the memories most similar to a natural-language query.
+pub async fn recall_memories(
+    State(state): State<AppState>,
+    Json(mut request): Json<RecallRequest>,
+) -> Result<Json<Vec<RecalledMemory>>, (StatusCode, String)> {
+    info!("Received recall_memories request: {:?}", request.query);
+
+    if request.query.is_none() && request.embedding.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "A recall needs a query or an embedding".to_string()));
+    }
+    // Semantic recall when an embedding model is configured, lexical otherwise
+    if let (None, Some(query), Some(embedder)) = (&request.embedding, &request.query, &state.embedder) {
+        let embedding = embedder
+            .embed(query)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to embed query: {}", e)))?;
+        request.embedding = Some(embedding);
+    }
+
+    state
+        .recall_memories(&request)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to recall memories: {}", e)))
+}
@@ -1,24 +1,82 @@
-use dashmap::DashMap;
-
 //use crate::models::{LogEntry, LogPayload};
 
 use agent_models::memory::memory_models::{LogEntry, LogPayload};
 
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::Json,
     routing::{get, post},
     Router,
 };
+use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::info;
 
+use crate::lifecycle::{
+    archive_conversation, delete_conversation, get_conversation_metadata, list_conversations,
+    ConversationArchiver, RetentionPolicy,
+};
+use crate::context::{get_conversation_context, CharRatioEstimator, TokenEstimator};
+use crate::embedder::TextEmbedder;
+use crate::long_term::{
+    delete_memory, get_memory, list_memories, recall_memories, store_memory, InMemoryLongTermStore,
+    LongTermMemoryStore,
+};
+use crate::search::{search_conversations, SearchIndex};
+use crate::stream::{stream_conversation, ConversationBroadcaster};
+use crate::blackboard::{
+    delete_blackboard_key, get_blackboard, get_blackboard_key, put_blackboard_key, watch_blackboard,
+    BlackboardEvent, BlackboardStore, InMemoryBlackboardStore,
+};
+use crate::summarizer::{get_conversation_summary, refresh_conversation_summary, ConversationSummarizer};
+use crate::models::{ConversationWindow, LogAck, LogRecord};
+use crate::storage::{ConversationStore, InMemoryConversationStore};
+
 /// Application state holding configurations
 /// The outcome could be converted into a ConversationContext
 #[derive(Clone)] // AppState needs to be Clone to be used as Axum state
 pub struct AppState {
-    pub db_memory: Arc<DashMap<String, Vec<LogEntry>>>,
+    /// Storage of the conversations, in memory or persisted.
+    pub store: Arc<dyn ConversationStore>,
+    /// Writes archived conversations to cold storage, None if archiving is disabled.
+    pub archiver: Option<Arc<ConversationArchiver>>,
+    /// Maintains the rolling summaries of the long conversations, None if summarization is disabled.
+    pub summarizer: Option<Arc<ConversationSummarizer>>,
+    /// Estimates the size of the assembled contexts.
+    pub token_estimator: Arc<dyn TokenEstimator>,
+    /// Long-term memories of the agents.
+    pub memories: Arc<dyn LongTermMemoryStore>,
+    /// Computes the embeddings of the memories and recall queries, None to compare their words.
+    pub embedder: Option<Arc<TextEmbedder>>,
+    /// Full-text index of the conversations.
+    pub search_index: Arc<SearchIndex>,
+    /// Pushes the appended entries to the subscribers of their conversation.
+    pub broadcaster: Arc<ConversationBroadcaster>,
+    /// Key/value blackboards of the conversations.
+    pub blackboard: Arc<dyn BlackboardStore>,
+    /// Pushes the changes of the blackboards to their watchers.
+    pub blackboard_events: Arc<ConversationBroadcaster<BlackboardEvent>>,
+}
+
+/// Settings of the memory server.
+#[derive(Clone, Default)]
+pub struct MemoryServerConfig {
+    /// Directory receiving the archived conversations, archiving is disabled if None.
+    pub archive_dir: Option<PathBuf>,
+    /// Limits on the stored conversations, enforced by a background task.
+    pub retention: RetentionPolicy,
+    /// LLM summarizing the long conversations, summarization is disabled if None.
+    pub summarizer: Option<Arc<ConversationSummarizer>>,
+    /// Estimates the size of the assembled contexts, a character ratio if None.
+    pub token_estimator: Option<Arc<dyn TokenEstimator>>,
+    /// Storage of the long-term memories, kept in memory if None.
+    pub memories: Option<Arc<dyn LongTermMemoryStore>>,
+    /// Embedding model of the long-term memories, recalls compare words if None.
+    pub embedder: Option<Arc<TextEmbedder>>,
+    /// Storage of the blackboards, kept in memory if None.
+    pub blackboard: Option<Arc<dyn BlackboardStore>>,
 }
 
 /// Memory_server
@@ -28,18 +86,76 @@ pub struct MemoryServer {
 }
 
 impl MemoryServer {
+    /// Creates a server keeping the conversations in memory.
     pub async fn new(uri: String) -> anyhow::Result<Self> {
-        let db_memory: DashMap<String, Vec<LogEntry>> = DashMap::new();
+        Self::with_store(uri, Arc::new(InMemoryConversationStore::new())).await
+    }
+
+    /// Creates a server keeping the conversations in the given store.
+    pub async fn with_store(uri: String, store: Arc<dyn ConversationStore>) -> anyhow::Result<Self> {
+        Self::with_config(uri, store, MemoryServerConfig::default()).await
+    }
+
+    /// Creates a server keeping the conversations in the given store, with archiving and retention settings.
+    pub async fn with_config(uri: String, store: Arc<dyn ConversationStore>, config: MemoryServerConfig) -> anyhow::Result<Self> {
+        let archiver = match config.archive_dir {
+            Some(archive_dir) => Some(Arc::new(ConversationArchiver::new(archive_dir)?)),
+            None => None,
+        };
+
+        // The index is kept in memory, rebuild it from the stored conversations
+        let search_index = Arc::new(SearchIndex::build(store.as_ref())?);
 
         // Create AppState
         let app_state = AppState {
-            db_memory: Arc::new(db_memory),
+            store,
+            archiver,
+            summarizer: config.summarizer,
+            token_estimator: config
+                .token_estimator
+                .unwrap_or_else(|| Arc::new(CharRatioEstimator::default())),
+            memories: config
+                .memories
+                .unwrap_or_else(|| Arc::new(InMemoryLongTermStore::new())),
+            embedder: config.embedder,
+            search_index,
+            broadcaster: Arc::new(ConversationBroadcaster::new()),
+            blackboard: config
+                .blackboard
+                .unwrap_or_else(|| Arc::new(InMemoryBlackboardStore::new())),
+            blackboard_events: Arc::new(ConversationBroadcaster::new()),
         };
 
+        // Cap the growth of the store
+        if config.retention.is_enabled() {
+            app_state.spawn_retention(config.retention);
+        }
+        app_state.spawn_blackboard_sweeper();
+
         let app = Router::new()
             .route("/", get(root))
             .route("/log", post(log_message))
-            .route("/conversation/{conversation_id}", get(get_conversation))
+            .route("/log/append", post(append_message))
+            .route("/conversations", get(list_conversations))
+            .route("/conversation/{conversation_id}", get(get_conversation).delete(delete_conversation))
+            .route("/conversation/{conversation_id}/metadata", get(get_conversation_metadata))
+            .route("/conversation/{conversation_id}/archive", post(archive_conversation))
+            .route(
+                "/conversation/{conversation_id}/summary",
+                get(get_conversation_summary).post(refresh_conversation_summary),
+            )
+            .route("/conversation/{conversation_id}/context", get(get_conversation_context))
+            .route("/conversation/{conversation_id}/stream", get(stream_conversation))
+            .route("/conversation/{conversation_id}/blackboard", get(get_blackboard))
+            .route("/conversation/{conversation_id}/blackboard/events", get(watch_blackboard))
+            .route(
+                "/conversation/{conversation_id}/blackboard/keys/{key}",
+                get(get_blackboard_key).put(put_blackboard_key).delete(delete_blackboard_key),
+            )
+            .route("/search", get(search_conversations))
+            .route("/memories", get(list_memories).post(store_memory))
+            .route("/memories/recall", post(recall_memories))
+            .route("/memories/{memory_id}", get(get_memory).delete(delete_memory))
             .with_state(app_state);
 
         Ok(Self { uri, app })
@@ -60,39 +176,73 @@ async fn root() -> &'static str {
     "Hello, Swarm Memory Service!"
 }
 
+/// Appends an entry and returns the whole conversation.
+/// Prefer /log/append, which only returns the acknowledgement of the new entry.
 async fn log_message(
     State(state): State<AppState>, // Extract the AppState
     Json(payload): Json<LogPayload>,
-) -> Json<Vec<LogEntry>> {
+) -> Result<Json<Vec<LogRecord>>, (StatusCode, String)> {
     
     info!("Received log_message for conversation : {:?}", payload.conversation_id);
 
-    let db_memory = state.db_memory.to_owned();
+    let conversation_id = payload.conversation_id.clone();
+    store_entry(&state, payload)?;
+    let conversation = state
+        .store
+        .get(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?
+        .unwrap_or_default();
 
+    Ok(Json(conversation))
+}
+
+/// Appends an entry and returns its seq, id and timestamp.
+async fn append_message(
+    State(state): State<AppState>,
+    Json(payload): Json<LogPayload>,
+) -> Result<Json<LogAck>, (StatusCode, String)> {
+    info!("Received append_message for conversation : {:?}", payload.conversation_id);
+
+    let conversation_id = payload.conversation_id.clone();
+    let record = store_entry(&state, payload)?;
+    Ok(Json(LogAck::new(&conversation_id, &record)))
+}
+
+/// Stores the entry of a log payload.
+fn store_entry(state: &AppState, payload: LogPayload) -> Result<LogRecord, (StatusCode, String)> {
     let new_entry = LogEntry {
         role: payload.role,
         content: payload.content,
         agent_id: payload.agent_id,
     };
 
-    let mut conversation = db_memory
-        .entry(payload.conversation_id)
-        .or_insert_with(Vec::new);
-    conversation.push(new_entry);
+    let record = state
+        .store
+        .append(&payload.conversation_id, new_entry)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store log entry: {}", e)))?;
 
-    Json(conversation.clone())
+    state.search_index.index(&payload.conversation_id, &record);
+    state.broadcaster.publish(&payload.conversation_id, &record);
+
+    // Keep the summary of long conversations up to date
+    state.schedule_summary(&payload.conversation_id);
+    Ok(record)
 }
 
+/// Returns the records of a conversation, optionally restricted to a window,
+/// e.g. /conversation/{id}?last=20 or /conversation/{id}?since_seq=42
 async fn get_conversation(
     State(state): State<AppState>, // Extract the AppState
     Path(conversation_id): Path<String>,
-) -> Json<Option<Vec<LogEntry>>> {
+    Query(window): Query<ConversationWindow>,
+) -> Result<Json<Option<Vec<LogRecord>>>, (StatusCode, String)> {
     
     info!("Received get_conversation request for id: {}", conversation_id);
 
-    let db_memory = state.db_memory.to_owned();
-
-    let conversation = db_memory.get(&conversation_id).map(|entry| entry.clone());
+    let conversation = state
+        .store
+        .get_window(&conversation_id, &window)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?;
 
-    Json(conversation)
+    Ok(Json(conversation))
 }
@@ -1,9 +1,25 @@
-use reqwest::Client;
+use std::pin::Pin;
+
+use futures::{stream, Stream};
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
 use anyhow::Result;
 
 //use crate::models::{LogEntry, LogPayload, Role};
-use agent_models::memory::memory_models::{LogEntry, LogPayload, Role};
+use agent_models::memory::memory_models::{LogPayload, Role};
+
+use crate::blackboard::{BlackboardConflict, BlackboardEntry, BlackboardEvent, BlackboardWrite, CasOutcome};
+use crate::context::{AssembledContext, ContextFormat};
+use crate::lifecycle::RemovedConversation;
+use crate::long_term::{Memory, MemoryRequest, MemoryScope, RecallRequest, RecalledMemory};
+use crate::search::{SearchParams, SearchResults};
+use crate::models::{ConversationMetadata, ConversationWindow, LogAck, LogRecord, SummarizedConversation};
+
+/// Records of a conversation pushed by the memory service, see AgentMemoryServiceClient::subscribe.
+pub type LogRecordStream = Pin<Box<dyn Stream<Item = Result<LogRecord>> + Send>>;
 
+/// Changes of a blackboard pushed by the memory service, see AgentMemoryServiceClient::watch_blackboard.
+pub type BlackboardEventStream = Pin<Box<dyn Stream<Item = Result<BlackboardEvent>> + Send>>;
 
 #[derive(Debug, Clone)]
 pub struct AgentMemoryServiceClient {
@@ -19,7 +35,7 @@ impl AgentMemoryServiceClient {
         }
     }
 
-    pub async fn log(&self, conversation_id: String, role: Role, content: String, agent_id: Option<String>) -> Result<Vec<LogEntry>> {
+    pub async fn log(&self, conversation_id: String, role: Role, content: String, agent_id: Option<String>) -> Result<Vec<LogRecord>> {
         let url = format!("{}/log", self.memory_service_url);
         let payload = LogPayload {
             conversation_id,
@@ -33,15 +49,354 @@ impl AgentMemoryServiceClient {
             .send()
             .await?;
 
-        Ok(response.json::<Vec<LogEntry>>().await?)
+        Ok(response.json::<Vec<LogRecord>>().await?)
+    }
+
+    pub async fn get_conversation(&self, conversation_id: &str) -> Result<Option<Vec<LogRecord>>> {
+        let url = format!("{}/conversation/{}", self.memory_service_url, conversation_id);
+        let response = self.client.get(&url)
+            .send()
+            .await?;
+
+        Ok(response.json::<Option<Vec<LogRecord>>>().await?)
+    }
+
+    /// Appends an entry and returns its acknowledgement, without echoing the conversation.
+    pub async fn append(&self, conversation_id: String, role: Role, content: String, agent_id: Option<String>) -> Result<LogAck> {
+        let url = format!("{}/log/append", self.memory_service_url);
+        let payload = LogPayload {
+            conversation_id,
+            role,
+            content,
+            agent_id,
+        };
+
+        let response = self.client.post(&url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<LogAck>().await?)
+    }
+
+    /// Returns the records of a conversation within the window, None if the conversation does not exist.
+    pub async fn get_conversation_window(&self, conversation_id: &str, window: &ConversationWindow) -> Result<Option<Vec<LogRecord>>> {
+        let url = format!("{}/conversation/{}", self.memory_service_url, conversation_id);
+        let response = self.client.get(&url)
+            .query(window)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Option<Vec<LogRecord>>>().await?)
+    }
+
+    /// Returns the last records of a conversation.
+    pub async fn get_last_entries(&self, conversation_id: &str, count: usize) -> Result<Option<Vec<LogRecord>>> {
+        self.get_conversation_window(conversation_id, &ConversationWindow::last(count)).await
+    }
+
+    /// Returns the records appended to a conversation after the given seq.
+    pub async fn get_entries_since(&self, conversation_id: &str, seq: u64) -> Result<Option<Vec<LogRecord>>> {
+        self.get_conversation_window(conversation_id, &ConversationWindow::since_seq(seq)).await
+    }
+
+    /// Lists the stored conversations, most recently active first.
+    pub async fn list_conversations(&self) -> Result<Vec<ConversationMetadata>> {
+        let url = format!("{}/conversations", self.memory_service_url);
+        let response = self.client.get(&url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<ConversationMetadata>>().await?)
+    }
+
+    /// Returns the metadata of a conversation, None if the conversation does not exist.
+    pub async fn get_conversation_metadata(&self, conversation_id: &str) -> Result<Option<ConversationMetadata>> {
+        let url = format!("{}/conversation/{}/metadata", self.memory_service_url, conversation_id);
+        let response = self.client.get(&url)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json::<ConversationMetadata>().await?))
     }
 
-    pub async fn get_conversation(&self, conversation_id: &str) -> Result<Option<Vec<LogEntry>>> {
+    /// Deletes a conversation. Returns false if the conversation did not exist.
+    pub async fn delete_conversation(&self, conversation_id: &str) -> Result<bool> {
         let url = format!("{}/conversation/{}", self.memory_service_url, conversation_id);
+        let response = self.client.delete(&url)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    /// Archives a conversation to the cold storage of the memory service and removes it.
+    pub async fn archive_conversation(&self, conversation_id: &str) -> Result<RemovedConversation> {
+        let url = format!("{}/conversation/{}/archive", self.memory_service_url, conversation_id);
+        let response = self.client.post(&url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<RemovedConversation>().await?)
+    }
+
+    /// Returns the rolling summary of a conversation and the entries it does not cover,
+    /// None if the conversation does not exist.
+    pub async fn get_conversation_summary(&self, conversation_id: &str) -> Result<Option<SummarizedConversation>> {
+        let url = format!("{}/conversation/{}/summary", self.memory_service_url, conversation_id);
         let response = self.client.get(&url)
             .send()
             .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json::<SummarizedConversation>().await?))
+    }
+
+    /// Returns a prompt-ready context of a conversation fitting in max_tokens,
+    /// None if the conversation does not exist.
+    pub async fn get_context(&self, conversation_id: &str, max_tokens: usize, format: ContextFormat) -> Result<Option<AssembledContext>> {
+        let url = format!("{}/conversation/{}/context", self.memory_service_url, conversation_id);
+        let format = match format {
+            ContextFormat::Text => "text",
+            ContextFormat::Messages => "messages",
+        };
+        let response = self.client.get(&url)
+            .query(&[("max_tokens", max_tokens.to_string()), ("format", format.to_string())])
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json::<AssembledContext>().await?))
+    }
+
+    /// Stores a long-term memory and returns it with its id.
+    pub async fn store_memory(&self, request: &MemoryRequest) -> Result<Memory> {
+        let url = format!("{}/memories", self.memory_service_url);
+        let response = self.client.post(&url)
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?;
 
-        Ok(response.json::<Option<Vec<LogEntry>>>().await?)
+        Ok(response.json::<Memory>().await?)
     }
-}
\ No newline at end of file
+
+    /// Recalls the long-term memories most similar to a query, best first.
+    pub async fn recall_memories(&self, request: &RecallRequest) -> Result<Vec<RecalledMemory>> {
+        let url = format!("{}/memories/recall", self.memory_service_url);
+        let response = self.client.post(&url)
+            .json(request)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<RecalledMemory>>().await?)
+    }
+
+    /// Lists the long-term memories of a scope, most recent first.
+    pub async fn list_memories(&self, scope: &MemoryScope) -> Result<Vec<Memory>> {
+        let url = format!("{}/memories", self.memory_service_url);
+        let response = self.client.get(&url)
+            .query(scope)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<Memory>>().await?)
+    }
+
+    /// Deletes a long-term memory. Returns false if the memory did not exist.
+    pub async fn delete_memory(&self, memory_id: &uuid::Uuid) -> Result<bool> {
+        let url = format!("{}/memories/{}", self.memory_service_url, memory_id);
+        let response = self.client.delete(&url)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    /// Searches the content of the conversations, e.g. with q = "\"invoice 4711\" overdue".
+    pub async fn search(&self, params: &SearchParams) -> Result<SearchResults> {
+        let url = format!("{}/search", self.memory_service_url);
+        let response = self.client.get(&url)
+            .query(params)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<SearchResults>().await?)
+    }
+
+    /// Subscribes to a conversation: the records appended after since_seq (only the new ones if None),
+    /// then every record as it is appended. The stream ends when the connection is closed,
+    /// subscribe again with the seq of the last record received to resume.
+    pub async fn subscribe(&self, conversation_id: &str, since_seq: Option<u64>) -> Result<LogRecordStream> {
+        let url = format!("{}/conversation/{}/stream", self.memory_service_url, conversation_id);
+        let mut request = self.client.get(&url)
+            .header(reqwest::header::ACCEPT, "text/event-stream");
+        if let Some(seq) = since_seq {
+            request = request.query(&[("since_seq", seq)]);
+        }
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(sse_stream(response, "entry"))
+    }
+
+    /// Returns the keys of the blackboard of a conversation.
+    pub async fn get_blackboard(&self, conversation_id: &str) -> Result<Vec<BlackboardEntry>> {
+        let url = format!("{}/conversation/{}/blackboard", self.memory_service_url, conversation_id);
+        let response = self.client.get(&url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<Vec<BlackboardEntry>>().await?)
+    }
+
+    /// Url of a key of a blackboard. Keys are natural names (e.g. "plan/step-1", "current plan"),
+    /// they are percent-encoded as a single path segment.
+    fn blackboard_key_url(&self, conversation_id: &str, key: &str) -> Result<Url> {
+        let mut url = Url::parse(&self.memory_service_url)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid memory service url: {}", self.memory_service_url))?
+            .pop_if_empty()
+            .extend(["conversation", conversation_id, "blackboard", "keys", key]);
+        Ok(url)
+    }
+
+    /// Returns a key of a blackboard, None if it does not exist or has expired.
+    pub async fn get_blackboard_key(&self, conversation_id: &str, key: &str) -> Result<Option<BlackboardEntry>> {
+        let url = self.blackboard_key_url(conversation_id, key)?;
+        let response = self.client.get(url)
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(response.error_for_status()?.json::<BlackboardEntry>().await?))
+    }
+
+    /// Writes a key of a blackboard. With an expected version, the write is a compare-and-swap
+    /// returning CasOutcome::Conflict if the key is no longer at this version.
+    pub async fn set_blackboard_key(&self, conversation_id: &str, key: &str, write: &BlackboardWrite) -> Result<CasOutcome> {
+        let url = self.blackboard_key_url(conversation_id, key)?;
+        let response = self.client.put(url)
+            .json(write)
+            .send()
+            .await?;
+
+        cas_outcome(response).await
+    }
+
+    /// Deletes a key of a blackboard, only if it is at the expected version when one is given.
+    pub async fn delete_blackboard_key(&self, conversation_id: &str, key: &str, expected_version: Option<u64>) -> Result<CasOutcome> {
+        let url = self.blackboard_key_url(conversation_id, key)?;
+        let mut request = self.client.delete(url);
+        if let Some(version) = expected_version {
+            request = request.query(&[("expected_version", version)]);
+        }
+        let response = request
+            .send()
+            .await?;
+
+        cas_outcome(response).await
+    }
+
+    /// Watches the changes of the blackboard of a conversation, or of one of its keys.
+    /// Changes made while not watching are not replayed, read the blackboard to catch up.
+    pub async fn watch_blackboard(&self, conversation_id: &str, key: Option<&str>) -> Result<BlackboardEventStream> {
+        let url = format!("{}/conversation/{}/blackboard/events", self.memory_service_url, conversation_id);
+        let mut request = self.client.get(&url)
+            .header(reqwest::header::ACCEPT, "text/event-stream");
+        if let Some(key) = key {
+            request = request.query(&[("key", key)]);
+        }
+        let response = request
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(sse_stream(response, "change"))
+    }
+}
+
+/// Converts the response to a blackboard write or delete into its outcome.
+async fn cas_outcome(response: reqwest::Response) -> Result<CasOutcome> {
+    match response.status() {
+        reqwest::StatusCode::NOT_FOUND => Ok(CasOutcome::Missing),
+        reqwest::StatusCode::CONFLICT => {
+            let conflict = response.json::<BlackboardConflict>().await?;
+            Ok(CasOutcome::Conflict { current: conflict.current })
+        }
+        _ => Ok(CasOutcome::Applied(response.error_for_status()?.json::<BlackboardEntry>().await?)),
+    }
+}
+
+/// Reads the Server-Sent Events of a response, until it ends or fails.
+/// Events named event_name are parsed as T, the others are skipped.
+fn sse_stream<T>(response: reqwest::Response, event_name: &'static str) -> Pin<Box<dyn Stream<Item = Result<T>> + Send>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let events = stream::unfold(Some((response, Vec::new())), move |state| async move {
+        let (mut response, mut buffer) = state?;
+        loop {
+            if let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+                let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                if let Some(item) = parse_sse_event(&event, event_name) {
+                    return Some((item, Some((response, buffer))));
+                }
+                continue;
+            }
+            match response.chunk().await {
+                Ok(Some(chunk)) => buffer.extend_from_slice(&chunk),
+                Ok(None) => return None,
+                Err(e) => return Some((Err(e.into()), None)),
+            }
+        }
+    });
+    Box::pin(events)
+}
+
+/// Parses the data of a Server-Sent Event named event_name, None for the other events and the keep-alives.
+fn parse_sse_event<T: DeserializeOwned>(event: &[u8], event_name: &str) -> Option<Result<T>> {
+    let event = String::from_utf8_lossy(event);
+    let mut event_type = "message";
+    let mut data = Vec::new();
+    for line in event.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            event_type = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+
+    match event_type {
+        "error" => Some(Err(anyhow::anyhow!("Memory service stream error: {}", data.join("\n")))),
+        name if name == event_name && !data.is_empty() => Some(serde_json::from_str::<T>(&data.join("\n")).map_err(Into::into)),
+        _ => None,
+    }
+}
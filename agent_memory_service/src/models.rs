@@ -1,5 +1,8 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Role {
@@ -38,4 +41,186 @@ pub struct LogPayload {
 pub struct ConversationContext {
     pub conversation_id: String,
     pub log_entries:Vec<LogEntry>,
-}
\ No newline at end of file
+}
+
+/// A log entry as stored by the memory server.
+/// The entry fields are flattened, so records can still be read as plain log entries.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogRecord {
+    /// Position of the entry in its conversation, starting at 1 and strictly increasing.
+    pub seq: u64,
+    /// Unique id of the entry, to reference it from other entries or services.
+    pub entry_id: Uuid,
+    /// Time at which the server appended the entry.
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub entry: agent_models::memory::memory_models::LogEntry,
+}
+
+impl LogRecord {
+    /// Stamps an entry appended at the given position.
+    pub fn new(seq: u64, entry: agent_models::memory::memory_models::LogEntry) -> Self {
+        LogRecord {
+            seq,
+            entry_id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            entry,
+        }
+    }
+}
+
+/// Acknowledgement of an appended entry, returned instead of the whole conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogAck {
+    pub conversation_id: String,
+    pub seq: u64,
+    pub entry_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl LogAck {
+    pub fn new(conversation_id: &str, record: &LogRecord) -> Self {
+        LogAck {
+            conversation_id: conversation_id.to_string(),
+            seq: record.seq,
+            entry_id: record.entry_id,
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+/// Window of a conversation to retrieve, e.g. /conversation/{id}?since_seq=42&last=10
+/// All bounds are optional and combined; `last` applies to the records within the other bounds.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ConversationWindow {
+    /// Only the last N records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<usize>,
+    /// Only the records after this seq (exclusive), to fetch what was appended since a previous read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_seq: Option<u64>,
+    /// Only the records before this seq (exclusive), to page backwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_seq: Option<u64>,
+    /// Only the records appended at or after this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    /// Only the records appended before this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ConversationWindow {
+    /// The last N records.
+    pub fn last(count: usize) -> Self {
+        ConversationWindow {
+            last: Some(count),
+            ..Default::default()
+        }
+    }
+
+    /// The records appended after the given seq.
+    pub fn since_seq(seq: u64) -> Self {
+        ConversationWindow {
+            since_seq: Some(seq),
+            ..Default::default()
+        }
+    }
+
+    /// Range of seq (inclusive) that may contain records of the window.
+    pub fn seq_range(&self) -> (u64, u64) {
+        let start = self.since_seq.map(|seq| seq.saturating_add(1)).unwrap_or(0);
+        let end = self.before_seq.map(|seq| seq.saturating_sub(1)).unwrap_or(u64::MAX);
+        (start, end)
+    }
+
+    /// True if the record is within the seq and time bounds (`last` aside).
+    pub fn contains(&self, record: &LogRecord) -> bool {
+        let (start, end) = self.seq_range();
+        record.seq >= start
+            && record.seq <= end
+            && self.from.is_none_or(|from| record.timestamp >= from)
+            && self.to.is_none_or(|to| record.timestamp < to)
+    }
+
+    /// Selects the records of the window among records ordered by seq.
+    pub fn apply(&self, records: impl IntoIterator<Item = LogRecord>) -> Vec<LogRecord> {
+        let mut selected: Vec<LogRecord> = records.into_iter().filter(|record| self.contains(record)).collect();
+        if let Some(last) = self.last {
+            selected.drain(..selected.len().saturating_sub(last));
+        }
+        selected
+    }
+}
+
+/// Metadata of a stored conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationMetadata {
+    pub conversation_id: String,
+    /// Agents having logged entries, along with the roles of the entries logged without an agent id.
+    pub participants: BTreeSet<String>,
+    pub entry_count: u64,
+    /// Seq of the last entry.
+    pub last_seq: u64,
+    pub first_activity: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+}
+
+impl ConversationMetadata {
+    /// Metadata of a conversation made of a single record.
+    pub fn new(conversation_id: &str, record: &LogRecord) -> Self {
+        let mut metadata = ConversationMetadata {
+            conversation_id: conversation_id.to_string(),
+            participants: BTreeSet::new(),
+            entry_count: 0,
+            last_seq: 0,
+            first_activity: record.timestamp,
+            last_activity: record.timestamp,
+        };
+        metadata.update(record);
+        metadata
+    }
+
+    /// Metadata of a conversation, None if it has no records.
+    pub fn from_records(conversation_id: &str, records: &[LogRecord]) -> Option<Self> {
+        let (first, others) = records.split_first()?;
+        let mut metadata = ConversationMetadata::new(conversation_id, first);
+        for record in others {
+            metadata.update(record);
+        }
+        Some(metadata)
+    }
+
+    /// Accounts for a record appended to the conversation.
+    pub fn update(&mut self, record: &LogRecord) {
+        let participant = match &record.entry.agent_id {
+            Some(agent_id) => agent_id.clone(),
+            None => format!("{:?}", record.entry.role),
+        };
+        self.participants.insert(participant);
+        self.entry_count += 1;
+        self.last_seq = self.last_seq.max(record.seq);
+        self.first_activity = self.first_activity.min(record.timestamp);
+        self.last_activity = self.last_activity.max(record.timestamp);
+    }
+}
+
+/// Rolling summary of a conversation, produced by an LLM.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationSummary {
+    pub conversation_id: String,
+    pub summary: String,
+    /// Seq of the last entry covered by the summary.
+    pub covered_until_seq: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Summary of a conversation along with the entries it does not cover yet.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SummarizedConversation {
+    pub conversation_id: String,
+    /// None while the conversation is below the summarization threshold.
+    pub summary: Option<ConversationSummary>,
+    /// Entries appended after the summary, or the whole conversation without summary.
+    pub recent_entries: Vec<LogRecord>,
+}
@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::RwLock;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use agent_models::memory::memory_models::Role;
+
+use crate::memory_server::AppState;
+use crate::models::LogRecord;
+use crate::storage::ConversationStore;
+
+/*
+search.rs
+full-text search across the conversations, e.g. /search?q=invoice 4711&role=user
+
+The index is an inverted index kept in memory: each word maps to the entries containing it,
+along with its positions in the entry so that "quoted phrases" match consecutive words only.
+It is rebuilt from the store when the server starts, and kept up to date as entries are
+appended and conversations removed.
+
+Every word and phrase of the query must match. Hits are ranked by TF-IDF, then most recent first,
+and come with a snippet of the entry where the matching words are highlighted.
+*/
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 200;
+
+/// Bytes of content shown before the first match, and in total, in a snippet.
+const SNIPPET_CONTEXT_BYTES: usize = 60;
+const SNIPPET_MAX_BYTES: usize = 200;
+
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_END: &str = "</mark>";
+
+/// Parameters of /search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchParams {
+    /// Words and "quoted phrases", all of which must match.
+    pub q: String,
+    /// "user", "agent" or "system".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// An entry matching a search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub conversation_id: String,
+    pub seq: u64,
+    pub entry_id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub role: Role,
+    pub agent_id: Option<String>,
+    pub score: f32,
+    /// Excerpt of the content, the matching words between <mark> and </mark>.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub query: String,
+    /// Number of matching entries, regardless of limit and offset.
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// A search query: words, and phrases of consecutive words.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<Vec<String>>,
+}
+
+impl ParsedQuery {
+    /// Parses a query, the text between double quotes being a phrase.
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = ParsedQuery::default();
+        for (i, part) in query.split('"').enumerate() {
+            let words: Vec<String> = tokenize(part).into_iter().map(|token| token.word).collect();
+            if i % 2 == 1 && words.len() > 1 {
+                parsed.phrases.push(words);
+            } else {
+                parsed.terms.extend(words);
+            }
+        }
+        parsed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+
+    /// Every word of the query, once.
+    fn words(&self) -> HashSet<&str> {
+        self.terms
+            .iter()
+            .chain(self.phrases.iter().flatten())
+            .map(|word| word.as_str())
+            .collect()
+    }
+}
+
+/// A word of a text and its byte range.
+struct Token {
+    word: String,
+    start: usize,
+    end: usize,
+}
+
+/// Splits a text into lowercase alphanumeric words.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push(Token {
+                    word: text[s..i].to_lowercase(),
+                    start: s,
+                    end: i,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// (conversation_id, seq) of an entry.
+type EntryKey = (String, u64);
+
+struct IndexedEntry {
+    record: LogRecord,
+    /// Distinct words of the entry, to remove it from the postings.
+    words: Vec<String>,
+}
+
+#[derive(Default)]
+struct IndexInner {
+    /// Word -> entries containing it -> positions of the word in the entry.
+    postings: HashMap<String, HashMap<EntryKey, Vec<u32>>>,
+    /// Ordered by key, so that the entries of a conversation are a range.
+    entries: BTreeMap<EntryKey, IndexedEntry>,
+}
+
+/// Inverted index of the content of the conversations.
+#[derive(Default)]
+pub struct SearchIndex {
+    inner: RwLock<IndexInner>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the index of every conversation of a store.
+    pub fn build(store: &dyn ConversationStore) -> anyhow::Result<Self> {
+        let index = SearchIndex::new();
+        for metadata in store.list_conversations()? {
+            for record in store.get(&metadata.conversation_id)?.unwrap_or_default() {
+                index.index(&metadata.conversation_id, &record);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Adds an entry to the index.
+    pub fn index(&self, conversation_id: &str, record: &LogRecord) {
+        let key = (conversation_id.to_string(), record.seq);
+        let mut positions: HashMap<String, Vec<u32>> = HashMap::new();
+        for (position, token) in tokenize(&record.entry.content).into_iter().enumerate() {
+            positions.entry(token.word).or_default().push(position as u32);
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let words = positions.keys().cloned().collect();
+        for (word, word_positions) in positions {
+            inner.postings.entry(word).or_default().insert(key.clone(), word_positions);
+        }
+        inner.entries.insert(
+            key,
+            IndexedEntry {
+                record: record.clone(),
+                words,
+            },
+        );
+    }
+
+    /// Removes the entries of a conversation from the index.
+    pub fn remove_conversation(&self, conversation_id: &str) {
+        let mut inner = self.inner.write().unwrap();
+        let range = (conversation_id.to_string(), 0)..=(conversation_id.to_string(), u64::MAX);
+        let keys: Vec<EntryKey> = inner.entries.range(range).map(|(key, _)| key.clone()).collect();
+        for key in keys {
+            let Some(entry) = inner.entries.remove(&key) else {
+                continue;
+            };
+            for word in entry.words {
+                if let Some(postings) = inner.postings.get_mut(&word) {
+                    postings.remove(&key);
+                    if postings.is_empty() {
+                        inner.postings.remove(&word);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the entries matching the query and the filters, best first.
+    pub fn search(&self, query: &ParsedQuery, params: &SearchParams) -> SearchResults {
+        let inner = self.inner.read().unwrap();
+        let words = query.words();
+
+        // Entries containing every word, starting from the rarest one
+        let mut postings: Vec<&HashMap<EntryKey, Vec<u32>>> = Vec::new();
+        for word in &words {
+            match inner.postings.get(*word) {
+                Some(word_postings) => postings.push(word_postings),
+                None => return SearchResults::empty(params),
+            }
+        }
+        postings.sort_by_key(|word_postings| word_postings.len());
+        let Some((rarest, others)) = postings.split_first() else {
+            return SearchResults::empty(params);
+        };
+
+        let entry_count = inner.entries.len() as f32;
+        let mut hits: Vec<SearchHit> = rarest
+            .keys()
+            .filter(|key| others.iter().all(|word_postings| word_postings.contains_key(*key)))
+            .filter_map(|key| inner.entries.get(key).map(|entry| (key, entry)))
+            .filter(|(key, entry)| matches_filters(&key.0, &entry.record, params))
+            .filter(|(key, _)| {
+                query
+                    .phrases
+                    .iter()
+                    .all(|phrase| contains_phrase(&inner.postings, key, phrase))
+            })
+            .map(|(key, entry)| {
+                let score = words
+                    .iter()
+                    .map(|word| {
+                        let word_postings = &inner.postings[*word];
+                        let term_frequency = word_postings[key].len() as f32;
+                        let inverse_document_frequency = (1.0 + entry_count / word_postings.len() as f32).ln();
+                        term_frequency * inverse_document_frequency
+                    })
+                    .sum();
+                SearchHit {
+                    conversation_id: key.0.clone(),
+                    seq: entry.record.seq,
+                    entry_id: entry.record.entry_id,
+                    timestamp: entry.record.timestamp,
+                    role: entry.record.entry.role.clone(),
+                    agent_id: entry.record.entry.agent_id.clone(),
+                    score,
+                    snippet: snippet(&entry.record.entry.content, &words),
+                }
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+        let total = hits.len();
+        let hits = hits
+            .into_iter()
+            .skip(params.offset.unwrap_or(0))
+            .take(params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT))
+            .collect();
+
+        SearchResults {
+            query: params.q.clone(),
+            total,
+            hits,
+        }
+    }
+}
+
+impl SearchResults {
+    fn empty(params: &SearchParams) -> Self {
+        SearchResults {
+            query: params.q.clone(),
+            total: 0,
+            hits: Vec::new(),
+        }
+    }
+}
+
+/// True if the entry passes the role, agent and conversation filters.
+fn matches_filters(conversation_id: &str, record: &LogRecord, params: &SearchParams) -> bool {
+    params
+        .role
+        .as_ref()
+        .is_none_or(|role| format!("{:?}", record.entry.role).eq_ignore_ascii_case(role))
+        && params
+            .agent_id
+            .as_ref()
+            .is_none_or(|agent_id| record.entry.agent_id.as_ref() == Some(agent_id))
+        && params
+            .conversation_id
+            .as_ref()
+            .is_none_or(|filter| filter == conversation_id)
+}
+
+/// True if the words of the phrase follow each other in the entry.
+fn contains_phrase(postings: &HashMap<String, HashMap<EntryKey, Vec<u32>>>, key: &EntryKey, phrase: &[String]) -> bool {
+    let positions: Vec<&Vec<u32>> = match phrase
+        .iter()
+        .map(|word| postings.get(word).and_then(|word_postings| word_postings.get(key)))
+        .collect::<Option<Vec<&Vec<u32>>>>()
+    {
+        Some(positions) => positions,
+        None => return false,
+    };
+    positions[0].iter().any(|&start| {
+        positions
+            .iter()
+            .enumerate()
+            .skip(1)
+            .all(|(offset, word_positions)| word_positions.binary_search(&(start + offset as u32)).is_ok())
+    })
+}
+
+/// Excerpt of a content around its first matching word, the matching words highlighted.
+fn snippet(content: &str, words: &HashSet<&str>) -> String {
+    let matches: Vec<Token> = tokenize(content)
+        .into_iter()
+        .filter(|token| words.contains(token.word.as_str()))
+        .collect();
+    let first_match = matches.first().map(|token| token.start).unwrap_or(0);
+
+    let start = char_boundary(content, first_match.saturating_sub(SNIPPET_CONTEXT_BYTES));
+    let end = char_boundary(content, (start + SNIPPET_MAX_BYTES).min(content.len()));
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push_str("...");
+    }
+    let mut cursor = start;
+    for token in matches.iter().filter(|token| token.start >= start && token.end <= end) {
+        snippet.push_str(&content[cursor..token.start]);
+        snippet.push_str(HIGHLIGHT_START);
+        snippet.push_str(&content[token.start..token.end]);
+        snippet.push_str(HIGHLIGHT_END);
+        cursor = token.end;
+    }
+    snippet.push_str(&content[cursor..end]);
+    if end < content.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+/// Closest char boundary at or before a byte index.
+fn char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Searches the content of every conversation,
+/// e.g. /search?q="invoice 4711"&agent_id=billing_agent
+pub async fn search_conversations(
+    State(state): State<AppState>,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResults>, (StatusCode, String)> {
+    info!("Received search request: {}", params.q);
+
+    let query = ParsedQuery::parse(&params.q);
+    if query.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "The query has no word to search".to_string()));
+    }
+    Ok(Json(state.search_index.search(&query, &params)))
+}
@@ -0,0 +1,336 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use redb::{Database, ReadableDatabase, ReadableTable, TableDefinition};
+
+use agent_models::memory::memory_models::LogEntry;
+
+use crate::models::{ConversationMetadata, ConversationSummary, ConversationWindow, LogRecord};
+
+/*
+storage.rs
+storage backends of the conversations kept by the memory service.
+
+* InMemoryConversationStore keeps the conversations in a DashMap, they are lost on restart.
+  It is meant for tests and throwaway deployments.
+* RedbConversationStore persists them in a redb database, like the evaluation service does.
+  Each log record is stored under (conversation_id, seq), so that appending to a
+  conversation does not rewrite it. The metadata of the conversations is kept up to date
+  in a separate table, so that listing them does not read every record.
+
+Stores stamp the appended entries with their sequence number, id and timestamp, within the
+same lock or write transaction as the append, so that sequence numbers are never reused
+and follow the order of the conversation.
+*/
+
+/// Key: (conversation_id, seq), Value: JSON encoded LogRecord.
+const CONVERSATIONS_TABLE: TableDefinition<(&str, u64), Vec<u8>> = TableDefinition::new("conversations");
+/// Key: conversation_id, Value: JSON encoded ConversationMetadata.
+const METADATA_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("conversation_metadata");
+/// Key: conversation_id, Value: JSON encoded ConversationSummary.
+const SUMMARIES_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("conversation_summaries");
+
+/// Storage of the conversation logs.
+pub trait ConversationStore: Send + Sync {
+    /// Appends an entry to a conversation, creating it if needed.
+    /// Returns the stored record.
+    fn append(&self, conversation_id: &str, entry: LogEntry) -> anyhow::Result<LogRecord>;
+
+    /// Returns the records of a conversation ordered by seq, None if the conversation does not exist.
+    fn get(&self, conversation_id: &str) -> anyhow::Result<Option<Vec<LogRecord>>>;
+
+    /// Returns the records of a conversation within the window, ordered by seq.
+    /// None if the conversation does not exist.
+    fn get_window(&self, conversation_id: &str, window: &ConversationWindow) -> anyhow::Result<Option<Vec<LogRecord>>> {
+        Ok(self.get(conversation_id)?.map(|records| window.apply(records)))
+    }
+
+    /// Returns the metadata of every conversation.
+    fn list_conversations(&self) -> anyhow::Result<Vec<ConversationMetadata>>;
+
+    /// Returns the metadata of a conversation, None if the conversation does not exist.
+    fn metadata(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationMetadata>>;
+
+    /// Deletes a conversation, along with its summary. Returns false if the conversation did not exist.
+    fn delete(&self, conversation_id: &str) -> anyhow::Result<bool>;
+
+    /// Deletes the records of a conversation up to until_seq included, along with its summary,
+    /// and returns the records left, appended after until_seq. The conversation is deleted if none is left.
+    fn delete_until(&self, conversation_id: &str, until_seq: u64) -> anyhow::Result<Vec<LogRecord>>;
+
+    /// Returns the rolling summary of a conversation, if any.
+    fn get_summary(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationSummary>>;
+
+    /// Stores the rolling summary of a conversation, replacing the previous one.
+    fn put_summary(&self, summary: ConversationSummary) -> anyhow::Result<()>;
+}
+
+/// Conversations kept in memory.
+#[derive(Default)]
+pub struct InMemoryConversationStore {
+    conversations: DashMap<String, Vec<LogRecord>>,
+    summaries: DashMap<String, ConversationSummary>,
+}
+
+impl InMemoryConversationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ConversationStore for InMemoryConversationStore {
+    fn append(&self, conversation_id: &str, entry: LogEntry) -> anyhow::Result<LogRecord> {
+        // The entry guard locks the conversation until the record is pushed
+        let mut conversation = self
+            .conversations
+            .entry(conversation_id.to_string())
+            .or_default();
+        let seq = conversation.last().map(|record| record.seq + 1).unwrap_or(1);
+        let record = LogRecord::new(seq, entry);
+        conversation.push(record.clone());
+        Ok(record)
+    }
+
+    fn get(&self, conversation_id: &str) -> anyhow::Result<Option<Vec<LogRecord>>> {
+        Ok(self.conversations.get(conversation_id).map(|entry| entry.clone()))
+    }
+
+    fn get_window(&self, conversation_id: &str, window: &ConversationWindow) -> anyhow::Result<Option<Vec<LogRecord>>> {
+        // Only the records of the window are cloned
+        Ok(self
+            .conversations
+            .get(conversation_id)
+            .map(|entry| window.apply(entry.iter().cloned())))
+    }
+
+    fn list_conversations(&self) -> anyhow::Result<Vec<ConversationMetadata>> {
+        Ok(self
+            .conversations
+            .iter()
+            .filter_map(|entry| ConversationMetadata::from_records(entry.key(), entry.value()))
+            .collect())
+    }
+
+    fn metadata(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationMetadata>> {
+        Ok(self
+            .conversations
+            .get(conversation_id)
+            .and_then(|entry| ConversationMetadata::from_records(conversation_id, entry.value())))
+    }
+
+    fn delete(&self, conversation_id: &str) -> anyhow::Result<bool> {
+        self.summaries.remove(conversation_id);
+        Ok(self.conversations.remove(conversation_id).is_some())
+    }
+
+    fn delete_until(&self, conversation_id: &str, until_seq: u64) -> anyhow::Result<Vec<LogRecord>> {
+        self.summaries.remove(conversation_id);
+        let Entry::Occupied(mut conversation) = self.conversations.entry(conversation_id.to_string()) else {
+            return Ok(Vec::new());
+        };
+        conversation.get_mut().retain(|record| record.seq > until_seq);
+        if conversation.get().is_empty() {
+            conversation.remove();
+            return Ok(Vec::new());
+        }
+        Ok(conversation.get().clone())
+    }
+
+    fn get_summary(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationSummary>> {
+        Ok(self.summaries.get(conversation_id).map(|entry| entry.clone()))
+    }
+
+    fn put_summary(&self, summary: ConversationSummary) -> anyhow::Result<()> {
+        self.summaries.insert(summary.conversation_id.clone(), summary);
+        Ok(())
+    }
+}
+
+/// Conversations persisted in a redb database.
+pub struct RedbConversationStore {
+    db: Arc<Database>,
+}
+
+impl RedbConversationStore {
+    /// Opens the database at the given path, creating it (and its directory) if needed.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let db = Database::create(path)?;
+        {
+            let write_txn = db.begin_write()?;
+            {
+                let _ = write_txn.open_table(CONVERSATIONS_TABLE)?;
+                let _ = write_txn.open_table(METADATA_TABLE)?;
+                let _ = write_txn.open_table(SUMMARIES_TABLE)?;
+            }
+            write_txn.commit()?;
+        }
+
+        Ok(RedbConversationStore { db: Arc::new(db) })
+    }
+
+    /// Database of the store, to keep other data of the memory service in the same file.
+    pub fn database(&self) -> Arc<Database> {
+        self.db.clone()
+    }
+
+    /// Reads the records of a conversation from a table, in order.
+    fn read_conversation<T>(table: &T, conversation_id: &str) -> anyhow::Result<Vec<LogRecord>>
+    where
+        T: ReadableTable<(&'static str, u64), Vec<u8>>,
+    {
+        let mut records = Vec::new();
+        for item in table.range((conversation_id, 0)..=(conversation_id, u64::MAX))? {
+            let (_, value) = item?;
+            records.push(serde_json::from_slice::<LogRecord>(&value.value())?);
+        }
+        Ok(records)
+    }
+}
+
+impl ConversationStore for RedbConversationStore {
+    fn append(&self, conversation_id: &str, entry: LogEntry) -> anyhow::Result<LogRecord> {
+        // redb serializes write transactions, two appends cannot get the same seq
+        let write_txn = self.db.begin_write()?;
+        let record = {
+            let mut table = write_txn.open_table(CONVERSATIONS_TABLE)?;
+            let seq = match table.range((conversation_id, 0)..=(conversation_id, u64::MAX))?.next_back() {
+                Some(last) => last?.0.value().1 + 1,
+                None => 1,
+            };
+            let record = LogRecord::new(seq, entry);
+            table.insert((conversation_id, seq), serde_json::to_vec(&record)?)?;
+
+            let mut metadata_table = write_txn.open_table(METADATA_TABLE)?;
+            let metadata = match metadata_table.get(conversation_id)? {
+                Some(value) => {
+                    let mut metadata = serde_json::from_slice::<ConversationMetadata>(&value.value())?;
+                    metadata.update(&record);
+                    metadata
+                }
+                None => ConversationMetadata::new(conversation_id, &record),
+            };
+            metadata_table.insert(conversation_id, serde_json::to_vec(&metadata)?)?;
+            record
+        };
+        write_txn.commit()?;
+        Ok(record)
+    }
+
+    fn get(&self, conversation_id: &str) -> anyhow::Result<Option<Vec<LogRecord>>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CONVERSATIONS_TABLE)?;
+        let records = Self::read_conversation(&table, conversation_id)?;
+        Ok(if records.is_empty() { None } else { Some(records) })
+    }
+
+    fn get_window(&self, conversation_id: &str, window: &ConversationWindow) -> anyhow::Result<Option<Vec<LogRecord>>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CONVERSATIONS_TABLE)?;
+        if table.range((conversation_id, 0)..=(conversation_id, u64::MAX))?.next().is_none() {
+            return Ok(None);
+        }
+
+        // Only the seq range of the window is read, from the end so that `last` stops early
+        let (start, end) = window.seq_range();
+        let limit = window.last.unwrap_or(usize::MAX);
+        let mut records = Vec::new();
+        if start <= end {
+            for item in table.range((conversation_id, start)..=(conversation_id, end))?.rev() {
+                if records.len() >= limit {
+                    break;
+                }
+                let (_, value) = item?;
+                let record = serde_json::from_slice::<LogRecord>(&value.value())?;
+                if window.contains(&record) {
+                    records.push(record);
+                }
+            }
+        }
+        records.reverse();
+        Ok(Some(records))
+    }
+
+    fn list_conversations(&self) -> anyhow::Result<Vec<ConversationMetadata>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(METADATA_TABLE)?;
+        let mut conversations = Vec::new();
+        for item in table.iter()? {
+            let (_, value) = item?;
+            conversations.push(serde_json::from_slice::<ConversationMetadata>(&value.value())?);
+        }
+        Ok(conversations)
+    }
+
+    fn metadata(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationMetadata>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(METADATA_TABLE)?;
+        match table.get(conversation_id)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, conversation_id: &str) -> anyhow::Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        let existed = {
+            let mut table = write_txn.open_table(CONVERSATIONS_TABLE)?;
+            table.retain_in((conversation_id, 0)..=(conversation_id, u64::MAX), |_, _| false)?;
+            let mut summaries_table = write_txn.open_table(SUMMARIES_TABLE)?;
+            summaries_table.remove(conversation_id)?;
+            let mut metadata_table = write_txn.open_table(METADATA_TABLE)?;
+            metadata_table.remove(conversation_id)?.is_some()
+        };
+        write_txn.commit()?;
+        Ok(existed)
+    }
+
+    fn delete_until(&self, conversation_id: &str, until_seq: u64) -> anyhow::Result<Vec<LogRecord>> {
+        // Appends wait for the write transaction, the records left are the ones appended before it
+        let write_txn = self.db.begin_write()?;
+        let remaining = {
+            let mut table = write_txn.open_table(CONVERSATIONS_TABLE)?;
+            table.retain_in((conversation_id, 0)..=(conversation_id, until_seq), |_, _| false)?;
+            let remaining = Self::read_conversation(&table, conversation_id)?;
+            let mut metadata_table = write_txn.open_table(METADATA_TABLE)?;
+            match ConversationMetadata::from_records(conversation_id, &remaining) {
+                Some(metadata) => {
+                    metadata_table.insert(conversation_id, serde_json::to_vec(&metadata)?)?;
+                }
+                None => {
+                    metadata_table.remove(conversation_id)?;
+                }
+            }
+            let mut summaries_table = write_txn.open_table(SUMMARIES_TABLE)?;
+            summaries_table.remove(conversation_id)?;
+            remaining
+        };
+        write_txn.commit()?;
+        Ok(remaining)
+    }
+
+    fn get_summary(&self, conversation_id: &str) -> anyhow::Result<Option<ConversationSummary>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SUMMARIES_TABLE)?;
+        match table.get(conversation_id)? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put_summary(&self, summary: ConversationSummary) -> anyhow::Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(SUMMARIES_TABLE)?;
+            table.insert(summary.conversation_id.as_str(), serde_json::to_vec(&summary)?)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use dashmap::DashMap;
+use futures::{stream, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use crate::memory_server::AppState;
+use crate::models::{ConversationWindow, LogRecord};
+
+/*
+stream.rs
+pushes the entries of a conversation to its subscribers as they are appended (Server-Sent Events).
+
+Each conversation being watched has a broadcast channel, fed by the appends. A subscriber
+gets one `entry` event per record, whose event id is the seq of the record. To resume after a
+disconnection, it reconnects with `?since_seq=` or the standard `Last-Event-ID` header: the
+records appended in between are read from the store before the live ones.
+Subscribers falling behind the channel, or seeing a gap in the seqs, catch up from the store
+too, so that no record is skipped and every record is sent once, in order.
+The channel of a conversation is dropped along with its last subscriber.
+*/
+
+/// Records buffered per conversation for slow subscribers.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Parameters of /conversation/{id}/stream
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamParams {
+    /// Sends the records appended after this seq first, only the new records if None.
+    pub since_seq: Option<u64>,
+}
+
+/// Broadcast channels of the watched conversations, of records by default.
+pub struct ConversationBroadcaster<T: Clone = LogRecord> {
+    channels: Arc<DashMap<String, broadcast::Sender<T>>>,
+}
+
+impl<T: Clone> Default for ConversationBroadcaster<T> {
+    fn default() -> Self {
+        ConversationBroadcaster { channels: Arc::new(DashMap::new()) }
+    }
+}
+
+impl<T: Clone> ConversationBroadcaster<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, conversation_id: &str) -> ConversationReceiver<T> {
+        let receiver = self
+            .channels
+            .entry(conversation_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+        ConversationReceiver {
+            receiver: Some(receiver),
+            conversation_id: conversation_id.to_string(),
+            channels: self.channels.clone(),
+        }
+    }
+
+    /// Sends a message to the subscribers of a conversation, if any.
+    pub fn publish(&self, conversation_id: &str, message: &T) {
+        if let Some(sender) = self.channels.get(conversation_id) {
+            // Fails only when nobody listens anymore
+            if sender.send(message.clone()).is_ok() {
+                return;
+            }
+        }
+        self.channels
+            .remove_if(conversation_id, |_, sender| sender.receiver_count() == 0);
+    }
+
+    /// Ends the streams of a conversation, e.g. once it is deleted.
+    pub fn close(&self, conversation_id: &str) {
+        self.channels.remove(conversation_id);
+    }
+}
+
+/// Receiver of the channel of a conversation. The channel is dropped along with its last receiver,
+/// so that conversations watched once do not keep a channel forever.
+pub struct ConversationReceiver<T: Clone> {
+    /// Only taken when dropped.
+    receiver: Option<broadcast::Receiver<T>>,
+    conversation_id: String,
+    channels: Arc<DashMap<String, broadcast::Sender<T>>>,
+}
+
+impl<T: Clone> Deref for ConversationReceiver<T> {
+    type Target = broadcast::Receiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.receiver.as_ref().expect("the receiver is only taken when dropped")
+    }
+}
+
+impl<T: Clone> DerefMut for ConversationReceiver<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.receiver.as_mut().expect("the receiver is only taken when dropped")
+    }
+}
+
+impl<T: Clone> Drop for ConversationReceiver<T> {
+    fn drop(&mut self) {
+        // Dropped first, so that a channel created again since a close keeps its receivers
+        drop(self.receiver.take());
+        self.channels
+            .remove_if(&self.conversation_id, |_, sender| sender.receiver_count() == 0);
+    }
+}
+
+/// State of the stream of a subscriber.
+struct Subscription {
+    state: AppState,
+    conversation_id: String,
+    receiver: ConversationReceiver<LogRecord>,
+    /// Records to send before waiting for the channel.
+    pending: VecDeque<LogRecord>,
+    /// Seq of the last record sent.
+    last_seq: u64,
+}
+
+impl Subscription {
+    /// Queues the records appended after the last one sent, as read from the store.
+    fn catch_up(&mut self) -> anyhow::Result<()> {
+        let records = self
+            .state
+            .store
+            .get_window(&self.conversation_id, &ConversationWindow::since_seq(self.last_seq))?
+            .unwrap_or_default();
+        self.pending.extend(records);
+        Ok(())
+    }
+
+    /// Waits for the next record to send, None once the stream is over.
+    async fn next_record(&mut self) -> Option<LogRecord> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                if record.seq > self.last_seq {
+                    self.last_seq = record.seq;
+                    return Some(record);
+                }
+                continue;
+            }
+
+            let outcome = match self.receiver.recv().await {
+                // Concurrent appends may be published out of order, the store has them all
+                Ok(record) if record.seq > self.last_seq + 1 => self.catch_up(),
+                Ok(record) => {
+                    self.pending.push_back(record);
+                    Ok(())
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber of conversation {} lagged by {} records", self.conversation_id, skipped);
+                    self.catch_up()
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            if let Err(e) = outcome {
+                warn!("Failed to read conversation {}, ending its stream: {}", self.conversation_id, e);
+                return None;
+            }
+        }
+    }
+}
+
+impl AppState {
+    /// Stream of the records of a conversation: the records appended after since_seq,
+    /// or only the new ones if None, then every record appended.
+    pub fn subscribe_conversation(
+        &self,
+        conversation_id: &str,
+        since_seq: Option<u64>,
+    ) -> anyhow::Result<impl Stream<Item = LogRecord> + Send + 'static> {
+        // Subscribe before reading the store, so that no append falls in between
+        let receiver = self.broadcaster.subscribe(conversation_id);
+        let mut subscription = Subscription {
+            state: self.clone(),
+            conversation_id: conversation_id.to_string(),
+            receiver,
+            pending: VecDeque::new(),
+            last_seq: 0,
+        };
+        match since_seq {
+            Some(seq) => {
+                subscription.last_seq = seq;
+                subscription.catch_up()?;
+            }
+            None => {
+                subscription.last_seq = self
+                    .store
+                    .metadata(conversation_id)?
+                    .map(|metadata| metadata.last_seq)
+                    .unwrap_or(0);
+            }
+        }
+
+        Ok(stream::unfold(subscription, |mut subscription| async move {
+            subscription.next_record().await.map(|record| (record, subscription))
+        }))
+    }
+}
+
+/// Streams the entries of a conversation as Server-Sent Events,
+/// e.g. /conversation/{id}/stream?since_seq=42
+pub async fn stream_conversation(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+    Query(params): Query<StreamParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    info!("Received stream_conversation request for id: {}", conversation_id);
+
+    // Browsers resume with the id of the last event received
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    let records = state
+        .subscribe_conversation(&conversation_id, last_event_id.or(params.since_seq))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?;
+
+    let events = records.map(|record| {
+        let event = Event::default().id(record.seq.to_string());
+        Ok(match serde_json::to_string(&record) {
+            Ok(data) => event.event("entry").data(data),
+            Err(e) => event.event("error").data(e.to_string()),
+        })
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
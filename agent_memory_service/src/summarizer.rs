@@ -0,0 +1,378 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::Utc;
+use dashmap::DashSet;
+use llm_api::chat::ChatLlmInteraction;
+use tracing::{info, trace, warn};
+
+use configuration::AgentConfig;
+
+use crate::memory_server::AppState;
+use crate::models::{ConversationSummary, ConversationWindow, LogRecord, SummarizedConversation};
+
+/*
+summarizer.rs
+maintains a rolling summary of the long conversations, produced by an LLM.
+
+Once a conversation has more than `threshold` entries not covered by its summary, the summary
+is updated in the background: the LLM merges the previous summary with the uncovered entries,
+except the `keep_recent` latest ones which are left verbatim.
+Readers get the summary along with the entries it does not cover from /conversation/{id}/summary.
+
+The LLM is any OpenAI-compatible chat completion endpoint, as for the judge of the evaluation service.
+*/
+
+const CONVERSATION_SUMMARY_PROMPT_TEMPLATE: &str = include_str!("../../configuration/prompts/conversation_summary_prompt.txt");
+
+/// Replaces the `{name}` placeholders of a template by their values.
+/// The template is read once, so placeholders appearing in the values (e.g. in the entries) are left as is.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let placeholder = rest[start + 1..]
+            .find('}')
+            .map(|end| &rest[start + 1..start + 1 + end])
+            .and_then(|name| values.iter().find(|(key, _)| *key == name).map(|(_, value)| (name, *value)));
+        match placeholder {
+            Some((name, value)) => {
+                filled.push_str(value);
+                rest = &rest[start + name.len() + 2..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Settings of the rolling summaries.
+#[derive(Debug, Clone)]
+pub struct SummaryConfig {
+    /// Number of entries not covered by the summary triggering an update.
+    pub threshold: usize,
+    /// Number of latest entries left out of the summary.
+    pub keep_recent: usize,
+    /// Maximum length of the summary, in words.
+    pub max_words: usize,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        SummaryConfig {
+            threshold: 50,
+            keep_recent: 10,
+            max_words: 400,
+        }
+    }
+}
+
+/// Produces the rolling summaries with an LLM.
+pub struct ConversationSummarizer {
+    llm_interaction: ChatLlmInteraction,
+    config: SummaryConfig,
+    /// Conversations being summarized, to avoid concurrent updates of the same summary.
+    in_progress: DashSet<String>,
+}
+
+impl ConversationSummarizer {
+    pub fn new(llm_url: String, model_id: String, api_key: String, config: SummaryConfig) -> Self {
+        ConversationSummarizer {
+            llm_interaction: ChatLlmInteraction::new(llm_url, model_id, api_key),
+            config,
+            in_progress: DashSet::new(),
+        }
+    }
+
+    /// Creates a summarizer using the LLM of an agent configuration file.
+    pub fn from_agent_config(agent_config: &AgentConfig, api_key: String, config: SummaryConfig) -> Self {
+        Self::new(agent_config.agent_llm_url(), agent_config.agent_model_id(), api_key, config)
+    }
+
+    pub fn config(&self) -> &SummaryConfig {
+        &self.config
+    }
+
+    /// Asks the LLM to merge the previous summary with new entries.
+    async fn summarize(&self, previous_summary: Option<&str>, records: &[LogRecord]) -> Result<String> {
+        let entries = records
+            .iter()
+            .map(|record| {
+                let participant = record.entry.agent_id.as_deref().unwrap_or("-");
+                format!("[{}] {} ({:?}): {}", record.seq, participant, record.entry.role, record.entry.content)
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let prompt = fill_template(
+            CONVERSATION_SUMMARY_PROMPT_TEMPLATE,
+            &[
+                ("previous_summary", previous_summary.unwrap_or("")),
+                ("entries", &entries),
+                ("max_words", &self.config.max_words.to_string()),
+            ],
+        );
+
+        let response = self.llm_interaction.call_api_simple("user".to_string(), prompt).await?;
+        let summary = response
+            .and_then(|msg| msg.content)
+            .ok_or_else(|| anyhow::anyhow!("LLM response content is empty"))?;
+
+        trace!("LLM summary response: {}", summary);
+        Ok(summary.trim().to_string())
+    }
+}
+
+impl AppState {
+    /// Updates the summary of a conversation if enough entries are not covered by it.
+    /// Returns the summary, updated or not.
+    pub async fn refresh_summary(&self, summarizer: &ConversationSummarizer, conversation_id: &str, force: bool) -> Result<Option<ConversationSummary>> {
+        let previous = self.store.get_summary(conversation_id)?;
+        let covered_until_seq = previous.as_ref().map(|summary| summary.covered_until_seq).unwrap_or(0);
+
+        let uncovered = self
+            .store
+            .get_window(conversation_id, &ConversationWindow::since_seq(covered_until_seq))?
+            .unwrap_or_default();
+        let keep_recent = summarizer.config.keep_recent;
+        if uncovered.len() <= keep_recent || (!force && uncovered.len() < summarizer.config.threshold) {
+            return Ok(previous);
+        }
+
+        // Only one update of a summary at a time, the others keep the current summary
+        if !summarizer.in_progress.insert(conversation_id.to_string()) {
+            return Ok(previous);
+        }
+
+        let to_summarize = &uncovered[..uncovered.len() - keep_recent];
+        let outcome = summarizer
+            .summarize(previous.as_ref().map(|summary| summary.summary.as_str()), to_summarize)
+            .await;
+        summarizer.in_progress.remove(conversation_id);
+
+        let summary = ConversationSummary {
+            conversation_id: conversation_id.to_string(),
+            summary: outcome?,
+            covered_until_seq: to_summarize.last().map(|record| record.seq).unwrap_or(covered_until_seq),
+            updated_at: Utc::now(),
+        };
+        self.store.put_summary(summary.clone())?;
+        info!("Updated the summary of conversation {} up to entry {}", conversation_id, summary.covered_until_seq);
+        Ok(Some(summary))
+    }
+
+    /// Updates the summary of a conversation in the background, if a summarizer is configured.
+    pub fn schedule_summary(&self, conversation_id: &str) {
+        let Some(summarizer) = self.summarizer.clone() else {
+            return;
+        };
+        let state = self.clone();
+        let conversation_id = conversation_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = state.refresh_summary(&summarizer, &conversation_id, false).await {
+                warn!("Failed to summarize conversation {}: {}", conversation_id, e);
+            }
+        });
+    }
+
+    /// Returns the summary of a conversation and the entries it does not cover.
+    /// None if the conversation does not exist.
+    pub fn summarized_conversation(&self, conversation_id: &str) -> Result<Option<SummarizedConversation>> {
+        let summary = self.store.get_summary(conversation_id)?;
+        let covered_until_seq = summary.as_ref().map(|summary| summary.covered_until_seq).unwrap_or(0);
+        let Some(recent_entries) = self
+            .store
+            .get_window(conversation_id, &ConversationWindow::since_seq(covered_until_seq))?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(SummarizedConversation {
+            conversation_id: conversation_id.to_string(),
+            summary,
+            recent_entries,
+        }))
+    }
+}
+
+/// Returns the rolling summary of a conversation along with the entries it does not cover.
+pub async fn get_conversation_summary(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<SummarizedConversation>, (StatusCode, String)> {
+    info!("Received get_conversation_summary request for id: {}", conversation_id);
+
+    state
+        .summarized_conversation(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)))
+}
+
+/// Summarizes a conversation now, whatever its size, and returns the updated summary.
+pub async fn refresh_conversation_summary(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<String>,
+) -> Result<Json<SummarizedConversation>, (StatusCode, String)> {
+    info!("Received refresh_conversation_summary request for id: {}", conversation_id);
+
+    let summarizer: Arc<ConversationSummarizer> = state
+        .summarizer
+        .clone()
+        .ok_or_else(|| (StatusCode::NOT_IMPLEMENTED, "Summarization is not configured".to_string()))?;
+    state
+        .refresh_summary(&summarizer, &conversation_id, true)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Failed to summarize conversation: {}", e)))?;
+
+    state
+        .summarized_conversation(&conversation_id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read conversation: {}", e)))?
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Conversation '{}' not found", conversation_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use agent_models::memory::memory_models::{LogEntry, Role};
+    use axum::{routing::{get, post}, Router};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::blackboard::InMemoryBlackboardStore;
+    use crate::context::CharRatioEstimator;
+    use crate::long_term::InMemoryLongTermStore;
+    use crate::search::SearchIndex;
+    use crate::storage::{ConversationStore, InMemoryConversationStore};
+    use crate::stream::ConversationBroadcaster;
+
+    /// Prompts received by the stub LLM.
+    #[derive(Clone, Default)]
+    struct StubLlm {
+        calls: Arc<AtomicUsize>,
+        last_prompt: Arc<Mutex<String>>,
+    }
+
+    /// Answers every chat completion with the same summary, as an OpenAI-compatible endpoint.
+    async fn chat_completions(State(stub): State<StubLlm>, Json(request): Json<Value>) -> Json<Value> {
+        stub.calls.fetch_add(1, Ordering::SeqCst);
+        *stub.last_prompt.lock().unwrap() = request["messages"][0]["content"].as_str().unwrap_or_default().to_string();
+        Json(json!({
+            "id": "chatcmpl-stub",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "stub-model",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "stub summary" },
+                "finish_reason": "stop"
+            }],
+            "usage": { "prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0 }
+        }))
+    }
+
+    /// Serves a router on a free local port, returning its address.
+    async fn serve(app: Router) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    fn app_state(store: Arc<dyn ConversationStore>) -> AppState {
+        AppState {
+            search_index: Arc::new(SearchIndex::build(store.as_ref()).unwrap()),
+            store,
+            archiver: None,
+            summarizer: None,
+            token_estimator: Arc::new(CharRatioEstimator::default()),
+            memories: Arc::new(InMemoryLongTermStore::new()),
+            embedder: None,
+            broadcaster: Arc::new(ConversationBroadcaster::new()),
+            blackboard: Arc::new(InMemoryBlackboardStore::new()),
+            blackboard_events: Arc::new(ConversationBroadcaster::new()),
+        }
+    }
+
+    fn append(state: &AppState, conversation_id: &str, count: usize) {
+        for i in 0..count {
+            let entry = LogEntry {
+                role: Role::User,
+                content: format!("message {}", i),
+                agent_id: None,
+            };
+            state.store.append(conversation_id, entry).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_summary_covers_all_but_the_recent_entries() {
+        let stub = StubLlm::default();
+        let llm_addr = serve(Router::new().route("/chat/completions", post(chat_completions)).with_state(stub.clone())).await;
+        let summarizer = ConversationSummarizer::new(
+            format!("http://{}/chat/completions", llm_addr),
+            "stub-model".to_string(),
+            "stub-key".to_string(),
+            SummaryConfig {
+                threshold: 5,
+                keep_recent: 2,
+                max_words: 100,
+            },
+        );
+
+        let state = app_state(Arc::new(InMemoryConversationStore::new()));
+        let memory_addr = serve(
+            Router::new()
+                .route("/conversation/{conversation_id}/summary", get(get_conversation_summary))
+                .with_state(state.clone()),
+        )
+        .await;
+
+        // Below the threshold, the LLM is not called
+        append(&state, "conversation-1", 4);
+        assert!(state.refresh_summary(&summarizer, "conversation-1", false).await.unwrap().is_none());
+        assert_eq!(stub.calls.load(Ordering::SeqCst), 0);
+
+        // At the threshold, every entry but the keep_recent latest ones is summarized
+        append(&state, "conversation-1", 2);
+        let summary = state
+            .refresh_summary(&summarizer, "conversation-1", false)
+            .await
+            .unwrap()
+            .expect("the conversation reached the threshold");
+        assert_eq!(stub.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(summary.summary, "stub summary");
+        assert_eq!(summary.covered_until_seq, 4);
+        let prompt = stub.last_prompt.lock().unwrap().clone();
+        assert!(prompt.contains("[4] -") && prompt.contains("message 3"));
+        assert!(!prompt.contains("[5] -"));
+        assert!(prompt.contains("at most 100 words"));
+
+        // The recent entries are never summarized, even when forced
+        state.refresh_summary(&summarizer, "conversation-1", true).await.unwrap();
+        assert_eq!(stub.calls.load(Ordering::SeqCst), 1);
+
+        let summarized: SummarizedConversation = reqwest::get(format!("http://{}/conversation/conversation-1/summary", memory_addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(summarized.summary.map(|summary| summary.covered_until_seq), Some(4));
+        let recent_seqs: Vec<u64> = summarized.recent_entries.iter().map(|record| record.seq).collect();
+        assert_eq!(recent_seqs, vec![5, 6]);
+    }
+}
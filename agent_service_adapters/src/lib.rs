@@ -1,3 +1,7 @@
+pub mod registration;
+
+use std::time::Duration;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use agent_evaluation_service::evaluation_service_client::agent_evaluation_client::AgentEvaluationServiceClient;
@@ -12,10 +16,15 @@ use agent_models::memory::memory_models::Role;
 
 use agent_core::business_logic::services::{EvaluationService, MemoryService, DiscoveryService};
 
-use agent_discovery_service::discovery_service_client::agent_discovery_client::AgentDiscoveryServiceClient;
+// Errors of the discovery client are kept in the anyhow errors, callers can downcast them
+// to DiscoveryClientError to tell a conflict or a validation error from a network failure.
+use agent_discovery_service::discovery_service_client::agent_discovery_client::{AgentDiscoveryServiceClient, Credentials};
+use agent_discovery_service::discovery_service_client::cache::{CacheStats, DiscoveryCache};
 //use agent_discovery_service::model::models::{AgentDefinition, TaskDefinition, ToolDefinition};
 use agent_models::registry::registry_models::{AgentDefinition, TaskDefinition, ToolDefinition};
 
+use crate::registration::RegistrationHandle;
+
 /********************************************/
 /* Service Adapter for Evaluation Service   */
 /********************************************/
@@ -58,7 +67,8 @@ impl AgentMemoryServiceAdapter {
 #[async_trait]
 impl MemoryService for AgentMemoryServiceAdapter {
     async fn log(&self, conversation_id: String, role: Role, text: String, agent_name: Option<String>) -> Result<()> {
-        self.client.log(conversation_id, role, text, agent_name).await.map(|_| ())
+        // Only the acknowledgement is returned, not the whole conversation
+        self.client.append(conversation_id, role, text, agent_name).await.map(|_| ())
     }
 }
 
@@ -68,12 +78,54 @@ impl MemoryService for AgentMemoryServiceAdapter {
 
 pub struct AgentDiscoveryServiceAdapter {
     client: AgentDiscoveryServiceClient,
+    /// Optional cache of the agent, task and tool lists.
+    cache: Option<DiscoveryCache>,
 }
 
 impl AgentDiscoveryServiceAdapter {
     pub fn new(url: &str) -> Self {
         let client = AgentDiscoveryServiceClient::new(url);
-        AgentDiscoveryServiceAdapter { client }
+        AgentDiscoveryServiceAdapter { client, cache: None }
+    }
+
+    /// Creates an adapter authenticating its registrations with the given credentials.
+    pub fn with_credentials(url: &str, credentials: Credentials) -> Self {
+        let client = AgentDiscoveryServiceClient::new(url).with_credentials(credentials);
+        AgentDiscoveryServiceAdapter { client, cache: None }
+    }
+
+    /// Creates an adapter from a configured client, e.g. with custom timeouts and retries:
+    /// `AgentDiscoveryServiceClient::builder(url).request_timeout(..).build()?`
+    pub fn from_client(client: AgentDiscoveryServiceClient) -> Self {
+        AgentDiscoveryServiceAdapter { client, cache: None }
+    }
+
+    /// Caches the agent, task and tool lists for the given TTL.
+    /// Stale lists are revalidated with the discovery service before being downloaded again.
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.cache = Some(DiscoveryCache::new(ttl));
+        self
+    }
+
+    /// Hit and miss counters of the cache, None if caching is disabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Registers an agent and keeps it registered until the returned handle is shut down or dropped,
+    /// re-registering it if the discovery service restarts.
+    pub async fn register_with_heartbeat(&self, agent_def: AgentDefinition, heartbeat_interval: Duration) -> Result<RegistrationHandle> {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_agents();
+        }
+        RegistrationHandle::register(self.client.clone(), agent_def, heartbeat_interval).await
+    }
+
+    /// Drops the cached lists, the next lookups are downloaded.
+    pub fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_all();
+        }
     }
 }
 
@@ -81,48 +133,76 @@ impl AgentDiscoveryServiceAdapter {
 impl DiscoveryService for AgentDiscoveryServiceAdapter {
     async fn register_agent(&self, agent_def: &AgentDefinition) -> Result<()> {
         self.client.register_agent_definition(agent_def).await?;
+        if let Some(cache) = &self.cache {
+            cache.invalidate_agents();
+        }
         Ok(())
     }
 
     async fn unregister_agent(&self, agent_def: &AgentDefinition) -> Result<()> {
         self.client.deregister_agent_definition(agent_def).await?;
+        if let Some(cache) = &self.cache {
+            cache.invalidate_agents();
+        }
         Ok(())
     }
 
     async fn get_agent_address(&self, agent_id: String) -> Result<Option<String>> {
-        let all_agents = self.client.list_agent_definitions().await?;
-        Ok(all_agents
-            .into_iter()
-            .find(|agent| agent.id == agent_id)
-            .map(|agent| {
-                if !agent.skills.is_empty() {
-                    Some(format!("agent://{}/", agent.id))
-                } else {
-                    None
-                }
-            }).flatten()
-        )
+        // With a cache, the agent is looked up in the cached list
+        if let Some(cache) = &self.cache {
+            let agents = cache.agents(&self.client).await?;
+            return Ok(agents
+                .into_iter()
+                .find(|agent| agent.id == agent_id && !agent.skills.is_empty())
+                .map(|agent| format!("agent://{}/", agent.id)));
+        }
+
+        // An unregistered agent has no address, any other failure is propagated
+        let agent = match self.client.get_agent_definition(&agent_id).await {
+            Ok(agent) => agent,
+            Err(e) if e.is_not_found() => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if agent.skills.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(format!("agent://{}/", agent.id)))
     }
 
     async fn discover_agents(&self) -> Result<Vec<AgentDefinition>> {
+        if let Some(cache) = &self.cache {
+            return Ok(cache.agents(&self.client).await?);
+        }
         Ok(self.client.list_agent_definitions().await?)
     }
 
     async fn register_task(&self, task_def: &TaskDefinition) -> Result<()> {
         self.client.register_task_definition(task_def).await?;
+        if let Some(cache) = &self.cache {
+            cache.invalidate_tasks();
+        }
         Ok(())
     }
 
     async fn list_tasks(&self) -> Result<Vec<TaskDefinition>> {
+        if let Some(cache) = &self.cache {
+            return Ok(cache.tasks(&self.client).await?);
+        }
         Ok(self.client.list_task_definitions().await?)
     }
 
     async fn register_tool(&self, tool_def: &ToolDefinition) -> Result<()> {
         self.client.register_tool_definition(tool_def).await?;
+        if let Some(cache) = &self.cache {
+            cache.invalidate_tools();
+        }
         Ok(())
     }
 
     async fn list_tools(&self) -> Result<Vec<ToolDefinition>> {
+        if let Some(cache) = &self.cache {
+            return Ok(cache.tools(&self.client).await?);
+        }
         Ok(self.client.list_tool_definitions().await?)
     }
 
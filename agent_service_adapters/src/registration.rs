@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use agent_discovery_service::discovery_service_client::agent_discovery_client::AgentDiscoveryServiceClient;
+use agent_models::registry::registry_models::AgentDefinition;
+
+/*
+registration.rs
+keeps an agent registered with the discovery service for as long as it runs.
+
+RegistrationHandle registers the agent, then checks at every heartbeat that the discovery
+service still knows it: the registry is in memory, so the agent is registered again
+after a restart of the discovery service. The agent is deregistered when the handle
+is shut down, when the shutdown signal it watches fires, or when the handle is dropped.
+*/
+
+/// Default interval between two heartbeats.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Registration of an agent, kept alive by a background heartbeat.
+pub struct RegistrationHandle {
+    agent_id: String,
+    shutdown: watch::Sender<bool>,
+    heartbeat: Option<JoinHandle<Result<()>>>,
+}
+
+impl RegistrationHandle {
+    /// Registers the agent and starts the heartbeat.
+    /// Fails if the first registration fails, e.g. when the discovery service rejects the definition.
+    pub async fn register(
+        client: AgentDiscoveryServiceClient,
+        agent_def: AgentDefinition,
+        heartbeat_interval: Duration,
+    ) -> Result<Self> {
+        client.register_agent_definition(&agent_def).await?;
+        info!("Registered agent {} with the discovery service at {}", agent_def.id, client.url());
+
+        let agent_id = agent_def.id.clone();
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let heartbeat = tokio::spawn(heartbeat(client, agent_def, heartbeat_interval, shutdown_rx));
+
+        Ok(RegistrationHandle {
+            agent_id,
+            shutdown,
+            heartbeat: Some(heartbeat),
+        })
+    }
+
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
+    /// Deregisters the agent when the signal completes, e.g. `tokio::signal::ctrl_c()`.
+    pub fn shutdown_on<S>(self, signal: S) -> Self
+    where
+        S: Future + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = signal => {
+                    let _ = shutdown.send(true);
+                }
+                // The handle was shut down or dropped first
+                _ = shutdown.closed() => {}
+            }
+        });
+        self
+    }
+
+    /// Stops the heartbeat and deregisters the agent, waiting for the deregistration.
+    pub async fn shutdown(mut self) -> Result<()> {
+        let _ = self.shutdown.send(true);
+        match self.heartbeat.take() {
+            Some(heartbeat) => heartbeat.await?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RegistrationHandle {
+    /// Stops the heartbeat, which deregisters the agent in the background.
+    /// The deregistration is lost if the runtime shuts down first, prefer shutdown() when possible.
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+/// Re-registers the agent whenever the discovery service lost it, until shutdown.
+async fn heartbeat(
+    client: AgentDiscoveryServiceClient,
+    agent_def: AgentDefinition,
+    heartbeat_interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let mut interval = tokio::time::interval(heartbeat_interval);
+    // The first tick completes immediately, the agent was just registered
+    interval.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            // Signaled, or the handle is gone
+            _ = shutdown.changed() => break,
+        }
+
+        match client.get_agent_definition(&agent_def.id).await {
+            Ok(_) => {}
+            Err(e) if e.is_not_found() => {
+                info!("Agent {} is no longer registered, registering it again", agent_def.id);
+                if let Err(e) = client.register_agent_definition(&agent_def).await {
+                    warn!("Failed to register agent {} again: {}", agent_def.id, e);
+                }
+            }
+            Err(e) => warn!("Heartbeat of agent {} failed: {}", agent_def.id, e),
+        }
+    }
+
+    client.deregister_agent_definition(&agent_def).await?;
+    info!("Deregistered agent {} from the discovery service", agent_def.id);
+    Ok(())
+}
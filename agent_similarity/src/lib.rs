@@ -0,0 +1,49 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/*
+agent_similarity
+text similarity helpers shared by the services: tokenization, hashed bag-of-words embeddings
+and cosine similarity.
+
+Hashed embeddings (feature hashing) only capture the words shared by two texts, not their
+meaning: they are the fallback when no embedding model is available.
+*/
+
+/// Words carrying no meaning for search purposes.
+pub const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "into", "is", "it", "of", "on",
+    "or", "that", "the", "this", "to", "with",
+];
+
+/// Splits a text into lowercase alphanumeric tokens, dropping stop words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .filter(|token| !STOP_WORDS.contains(&token.as_str()))
+        .collect()
+}
+
+/// Hashes the tokens of a text into a vector of the given dimension,
+/// so that texts sharing words get similar vectors.
+pub fn hashed_embedding(text: &str, dimension: usize) -> Vec<f32> {
+    let mut vec = vec![0.0; dimension];
+    for token in tokenize(text) {
+        let mut hasher = DefaultHasher::new();
+        token.hash(&mut hasher);
+        vec[(hasher.finish() % dimension as u64) as usize] += 1.0;
+    }
+    vec
+}
+
+/// Cosine similarity of two vectors, 0 when one of them is null.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x.powi(2)).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x.powi(2)).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot_product / (norm_a * norm_b)
+}